@@ -1,42 +1,200 @@
-use rand::prelude::*;
+use std::f32::consts::FRAC_PI_2;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
 
 use bevy::prelude::*;
 use bevy::render::settings::*;
 use bevy::render::RenderPlugin;
+use bevy::tasks::{block_on, poll_once, AsyncComputeTaskPool, Task};
+use bevy::ui::RelativeCursorPosition;
 
+use wfc_core::{analyze_rules, validate, Explanation, RuleReport, Violation};
+use wfc_core::{cell_selector_by_name, value_selector_by_name, CELL_SELECTORS, VALUE_SELECTORS};
+use wfc_core::{export_png, RenderOptions, Solver, Step, Tile, Tileset, WfcError};
+
+// sprite and rotation of every tile variant in the tileset
 #[derive(Component)]
-struct Sprites(Vec<Sprite>);
-
-#[derive(Debug, Clone, PartialEq)]
-enum TileOption {
-    Blank,
-    Down,
-    Left,
-    Right,
-    Up,
-}
-
-#[derive(Debug, Clone)]
-struct Tile {
-    collapsed: bool,
-    options: Vec<TileOption>,
-    i: usize,
-    j: usize,
-    can_be_collapsed: bool,
-}
-const DIM: usize = 7;
+struct Sprites(Vec<(Sprite, Quat)>);
+
 const SPRITE_SIZE: f32 = 50.;
 const SPRITE_GAP: f32 = 10.0;
 const GREEN: Color = Color::srgb(0., 0.2, 0.);
+const MAX_BACKTRACKS: usize = 1000;
+const VISIBLE_WIDTH: f32 = 1200.0;
+const VISIBLE_HEIGHT: f32 = 560.0;
+const MIN_SPEED: f32 = 1.0;
+const MAX_SPEED: f32 = 1000.0;
 
 #[derive(Component)]
-struct Grid(Vec<Tile>);
+struct Grid(Solver);
+
+// Replaces the grid on screen, sent by Restart and Load.
+#[derive(Event)]
+struct ReplaceGrid {
+    grid: wfc_core::Grid,
+    message: String,
+}
+
+// Set WFC_WIDTH and WFC_HEIGHT to change the grid size, default is 7x7.
+// WFC_TILESET is the path to the tileset manifest, WFC_CELL_SELECTOR is
+// the strategy the solver uses to pick cells on its own and
+// WFC_VALUE_SELECTOR the strategy for picking the option of a cell.
+// WFC_SAVE is the file for Save and Load, default is wfc_save.ron, and
+// WFC_EXPORT the PNG file Export writes, default is wfc_map.png.
+#[derive(Resource)]
+struct GridConfig {
+    width: usize,
+    height: usize,
+    tileset: Tileset,
+    cell_selector: String,
+    value_selector: String,
+    save_path: String,
+    export_path: String,
+}
+
+impl GridConfig {
+    fn from_env() -> GridConfig {
+        let dim = |name: &str| -> usize {
+            match std::env::var(name) {
+                Ok(value) => value
+                    .parse::<NonZeroUsize>()
+                    .expect("grid dimensions must be positive numbers")
+                    .get(),
+                Err(_) => 7,
+            }
+        };
+        let tileset_path: String =
+            std::env::var("WFC_TILESET").unwrap_or(String::from("assets/tiles.ron"));
+        let tileset: Tileset = match Tileset::load(&tileset_path) {
+            Ok(tileset) => tileset,
+            Err(err) => panic!("ERROR: can't load tileset {tileset_path}: {err}"),
+        };
+        let report: RuleReport = analyze_rules(&tileset);
+        for line in report.to_string().lines() {
+            println!("WARNING: tileset {tileset_path}: {line}");
+        }
+        let cell_selector: String =
+            std::env::var("WFC_CELL_SELECTOR").unwrap_or(String::from("min-entropy"));
+        if let Err(err) = cell_selector_by_name(&cell_selector) {
+            panic!("ERROR: {err}, use one of {:?}", CELL_SELECTORS);
+        }
+        let value_selector: String =
+            std::env::var("WFC_VALUE_SELECTOR").unwrap_or(String::from("weighted"));
+        if let Err(err) = value_selector_by_name(&value_selector) {
+            panic!("ERROR: {err}, use one of {:?}", VALUE_SELECTORS);
+        }
+        GridConfig {
+            width: dim("WFC_WIDTH"),
+            height: dim("WFC_HEIGHT"),
+            tileset,
+            cell_selector,
+            value_selector,
+            save_path: std::env::var("WFC_SAVE").unwrap_or(String::from("wfc_save.ron")),
+            export_path: std::env::var("WFC_EXPORT").unwrap_or(String::from("wfc_map.png")),
+        }
+    }
+
+    fn half_len(cells: usize) -> f32 {
+        ((cells as f32) * SPRITE_SIZE + ((cells - 1) as f32) * SPRITE_GAP) / 2.0
+    }
+}
+
+// Auto mode collapses the cells the solver picks on its own, speed is in
+// cells per second. Step collapses a single cell while paused.
+#[derive(Resource)]
+struct AutoMode {
+    playing: bool,
+    step: bool,
+    speed: f32,
+    // fraction of a cell left over from the last frames
+    budget: f32,
+}
+
+impl Default for AutoMode {
+    fn default() -> AutoMode {
+        AutoMode {
+            playing: false,
+            step: false,
+            speed: 10.0,
+            budget: 0.0,
+        }
+    }
+}
+
+impl AutoMode {
+    // the speed slider is logarithmic, 0 is MIN_SPEED and 1 is MAX_SPEED
+    fn speed_at(fraction: f32) -> f32 {
+        MIN_SPEED * (MAX_SPEED / MIN_SPEED).powf(fraction.clamp(0.0, 1.0))
+    }
+
+    fn fraction(&self) -> f32 {
+        (self.speed / MIN_SPEED).ln() / (MAX_SPEED / MIN_SPEED).ln()
+    }
+}
+
+struct SolveProgress {
+    collapsed: usize,
+    backtracks: usize,
+}
+
+// A full solve running on the AsyncComputeTaskPool. The solver is moved
+// into the task and put back into a Grid when the task ends, setting cancel
+// stops the task after its current step.
+#[derive(Component)]
+struct SolveTask {
+    task: Task<(Solver, Result<bool, WfcError>)>,
+    progress: Mutex<Receiver<SolveProgress>>,
+    cancel: Arc<AtomicBool>,
+    cells: usize,
+}
+
+#[derive(Component)]
+struct TileSprite;
+
+#[derive(Component)]
+struct RestartButton;
+
+#[derive(Component)]
+struct PlayButton;
+
+#[derive(Component)]
+struct StepButton;
+
+#[derive(Component)]
+struct SolveButton;
+
+#[derive(Component)]
+struct UndoButton;
+
+#[derive(Component)]
+struct RedoButton;
+
+#[derive(Component)]
+struct SaveButton;
+
+#[derive(Component)]
+struct LoadButton;
+
+#[derive(Component)]
+struct ExportButton;
+
+#[derive(Component)]
+struct SpeedSlider;
+
+#[derive(Component)]
+struct SpeedFill;
+
+#[derive(Component)]
+struct SpeedText;
+
+#[derive(Component)]
+struct SeedText;
 
 #[derive(Component)]
 struct RectangleIndexes {
     grid_ind: usize,
-    i: usize,
-    j: usize,
 }
 
 fn main() {
@@ -52,18 +210,59 @@ fn main() {
             }),
             MeshPickingPlugin,
         ))
+        .insert_resource(GridConfig::from_env())
+        .init_resource::<AutoMode>()
+        .add_event::<ReplaceGrid>()
         .add_systems(Startup, setup)
-        .add_systems(Update, button_system)
+        .add_systems(
+            Update,
+            (
+                button_system,
+                save_load_system,
+                export_system,
+                replace_grid_system,
+                auto_button_system,
+                solve_button_system,
+                undo_system,
+                speed_slider_system,
+                auto_collapse_system,
+                solve_poll_system,
+                auto_ui_system,
+            )
+                .chain(),
+        )
         .run();
 }
 
+// Set WFC_SEED to replay a map, otherwise every grid gets a random seed.
+fn pick_seed() -> u64 {
+    match std::env::var("WFC_SEED") {
+        Ok(seed) => seed.parse().expect("WFC_SEED must be a u64"),
+        Err(_) => rand::random(),
+    }
+}
+
+// zoom out if the grid doesn't fit into the window
+fn camera_scale(width: usize, height: usize) -> f32 {
+    (GridConfig::half_len(width) * 2.0 / VISIBLE_WIDTH)
+        .max(GridConfig::half_len(height) * 2.0 / VISIBLE_HEIGHT)
+        .max(1.0)
+}
+
 fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    meshes: ResMut<Assets<Mesh>>,
-    materials: ResMut<Assets<ColorMaterial>>,
+    config: Res<GridConfig>,
+    auto: Res<AutoMode>,
+    mut replace_writer: EventWriter<ReplaceGrid>,
 ) {
-    commands.spawn(Camera2d);
+    commands.spawn((
+        Camera2d,
+        OrthographicProjection {
+            scale: camera_scale(config.width, config.height),
+            ..OrthographicProjection::default_2d()
+        },
+    ));
 
     // Text with one section
     commands.spawn((
@@ -85,9 +284,26 @@ fn setup(
         Label,
     ));
 
+    let seed: u64 = pick_seed();
+    commands.spawn((
+        Text::new(format!("Seed: {seed}")),
+        TextFont {
+            font_size: 24.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            left: Val::Px(10.0),
+            ..default()
+        },
+        SeedText,
+    ));
+
     commands
         .spawn((
             Button,
+            RestartButton,
             Node {
                 top: Val::Px(60.0),
                 border: UiRect::all(Val::Px(5.0)),
@@ -108,94 +324,158 @@ fn setup(
             ));
         });
 
-    let mut sprites: Vec<Sprite> = vec![];
-
-    let mut sprite_0: Sprite = Sprite::from_image(asset_server.load("blank.png"));
-    sprite_0.custom_size = Some(Vec2::new(SPRITE_SIZE, SPRITE_SIZE));
-    sprites.push(sprite_0);
-
-    let mut sprite_1: Sprite = Sprite::from_image(asset_server.load("down.png"));
-    sprite_1.custom_size = Some(Vec2::new(SPRITE_SIZE, SPRITE_SIZE));
-    sprites.push(sprite_1);
-
-    let mut sprite_2: Sprite = Sprite::from_image(asset_server.load("left.png"));
-    sprite_2.custom_size = Some(Vec2::new(SPRITE_SIZE, SPRITE_SIZE));
-    sprites.push(sprite_2);
-
-    let mut sprite_3: Sprite = Sprite::from_image(asset_server.load("right.png"));
-    sprite_3.custom_size = Some(Vec2::new(SPRITE_SIZE, SPRITE_SIZE));
-    sprites.push(sprite_3);
+    // auto mode controls in the bottom right corner
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            right: Val::Px(10.0),
+            column_gap: Val::Px(10.0),
+            align_items: AlignItems::Center,
+            ..default()
+        })
+        .with_children(|builder| {
+            spawn_button(builder, PlayButton, "Play");
+            spawn_button(builder, StepButton, "Step");
+            spawn_button(builder, SolveButton, "Solve");
+            spawn_button(builder, UndoButton, "Undo");
+            spawn_button(builder, RedoButton, "Redo");
+            spawn_button(builder, SaveButton, "Save");
+            spawn_button(builder, LoadButton, "Load");
+            spawn_button(builder, ExportButton, "Export");
+            builder
+                .spawn((
+                    Node {
+                        width: Val::Px(200.0),
+                        height: Val::Px(20.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.2, 0.2, 0.2)),
+                    Interaction::default(),
+                    RelativeCursorPosition::default(),
+                    SpeedSlider,
+                ))
+                .with_children(|builder| {
+                    builder.spawn((
+                        Node {
+                            width: Val::Percent(auto.fraction() * 100.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(0., 0.5, 0.)),
+                        SpeedFill,
+                    ));
+                });
+            builder.spawn((
+                Text::new(format!("{:.0} cells/s", auto.speed)),
+                TextFont {
+                    font_size: 24.0,
+                    ..default()
+                },
+                SpeedText,
+            ));
+        });
 
-    let mut sprite_4: Sprite = Sprite::from_image(asset_server.load("up.png"));
-    sprite_4.custom_size = Some(Vec2::new(SPRITE_SIZE, SPRITE_SIZE));
-    sprites.push(sprite_4);
+    // sprites are in the same order as tiles in the tileset,
+    // so a TileOption is also an index into Sprites
+    let mut sprites: Vec<(Sprite, Quat)> = vec![];
+    for tile in &config.tileset.tiles {
+        let mut sprite: Sprite = Sprite::from_image(asset_server.load(&tile.image));
+        sprite.custom_size = Some(Vec2::new(SPRITE_SIZE, SPRITE_SIZE));
+        sprite.flip_x = tile.mirrored;
+        let rotation: Quat = Quat::from_rotation_z(-(tile.rotation as f32) * FRAC_PI_2);
+        sprites.push((sprite, rotation));
+    }
 
     commands.spawn(Sprites(sprites));
 
-    make_grid(commands, meshes, materials);
+    replace_writer.send(ReplaceGrid {
+        grid: wfc_core::Grid::new(config.width, config.height, seed, &config.tileset),
+        message: String::from("Plz click on some rect"),
+    });
+}
+
+fn spawn_button(builder: &mut ChildBuilder, marker: impl Component, label: &str) {
+    builder
+        .spawn((
+            Button,
+            marker,
+            Node {
+                border: UiRect::all(Val::Px(5.0)),
+                padding: UiRect::horizontal(Val::Px(10.0)),
+                ..default()
+            },
+            BorderColor(Color::BLACK),
+            BorderRadius::MAX,
+        ))
+        .with_children(|builder| {
+            builder.spawn((
+                Text::new(label),
+                TextFont {
+                    font_size: 24.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.9, 0.9, 0.9)),
+            ));
+        });
 }
 
+// Spawns the rects and sprites of the grid, a new grid is all green and a
+// loaded grid looks the same as when it was saved.
 fn make_grid(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    config: &GridConfig,
+    sprites: &Sprites,
+    grid: wfc_core::Grid,
 ) {
-    let mut grid: Vec<Tile> = vec![];
-    let mut half_of_matrix_len: f32 =
-        ((DIM as f32) * SPRITE_SIZE + ((DIM - 1) as f32) * SPRITE_GAP) / 2.0;
-    half_of_matrix_len -= SPRITE_SIZE / 2.0;
-    let mut x_start: f32 = 0.0 - half_of_matrix_len;
-    let mut y_start: f32 = 0.0 + half_of_matrix_len;
-    for i in 0..DIM {
-        for j in 0..DIM {
-            let grid_ind: usize = i * DIM + j;
+    let half_of_matrix_width: f32 = GridConfig::half_len(grid.width) - SPRITE_SIZE / 2.0;
+    let half_of_matrix_height: f32 = GridConfig::half_len(grid.height) - SPRITE_SIZE / 2.0;
+    let mut x_start: f32 = 0.0 - half_of_matrix_width;
+    let mut y_start: f32 = 0.0 + half_of_matrix_height;
+    for i in 0..grid.height {
+        for j in 0..grid.width {
+            let grid_ind: usize = grid.index(i, j);
+            let tile: &Tile = &grid.tiles[grid_ind];
+            let transform: Transform = Transform::from_xyz(x_start, y_start, 0.0);
+            if tile.collapsed {
+                spawn_tile_sprite(commands, sprites, tile, &transform);
+            }
             commands
                 .spawn((
                     Mesh2d(meshes.add(Rectangle::new(SPRITE_SIZE, SPRITE_SIZE))),
-                    MeshMaterial2d(materials.add(GREEN)),
-                    Transform::from_xyz(x_start, y_start, 0.0),
-                    RectangleIndexes {
-                        grid_ind: grid_ind,
-                        i: i,
-                        j: j,
-                    },
+                    MeshMaterial2d(materials.add(cell_color(tile))),
+                    transform,
+                    RectangleIndexes { grid_ind },
                 ))
                 .observe(on_rect_click);
 
-            grid.push(Tile {
-                collapsed: false,
-                options: vec![
-                    TileOption::Blank,
-                    TileOption::Down,
-                    TileOption::Left,
-                    TileOption::Right,
-                    TileOption::Up,
-                ],
-                i: i,
-                j: j,
-                can_be_collapsed: true,
-            });
-
             x_start += SPRITE_SIZE + SPRITE_GAP;
         }
-        x_start = 0.0 - half_of_matrix_len;
+        x_start = 0.0 - half_of_matrix_width;
         y_start -= SPRITE_SIZE + SPRITE_GAP;
     }
 
-    commands.spawn(Grid(grid));
+    let solver: Solver = Solver::new(grid, config.tileset.clone(), MAX_BACKTRACKS)
+        .with_cell_selector(cell_selector_by_name(&config.cell_selector).unwrap())
+        .with_value_selector(value_selector_by_name(&config.value_selector).unwrap());
+    commands.spawn(Grid(solver));
 }
 
+#[allow(clippy::too_many_arguments)]
 fn on_rect_click(
     click: Trigger<Pointer<Click>>,
-    mut transforms: Query<&mut Transform>,
-    mut rect_indexes_q: Query<&RectangleIndexes>,
+    rect_indexes_q: Query<&RectangleIndexes>,
     mut commands: Commands,
     spites_q: Query<&Sprites>,
     mut grid_q: Query<&mut Grid>,
-    mut mat_query: Query<
-        (&mut MeshMaterial2d<ColorMaterial>, &RectangleIndexes),
-        With<RectangleIndexes>,
-    >,
+    rect_query: Query<(
+        &MeshMaterial2d<ColorMaterial>,
+        &RectangleIndexes,
+        &Transform,
+    )>,
+    tile_sprite_query: Query<Entity, With<TileSprite>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut text_query: Query<&mut Text, With<Label>>,
 ) {
@@ -203,458 +483,630 @@ fn on_rect_click(
 
     println!("click on rect happened");
 
-    let rect_indexes: &RectangleIndexes = rect_indexes_q.get_mut(click.target).unwrap();
-    let mut grid: &mut Vec<Tile> = &mut grid_q.single_mut().0;
-
-    if grid[rect_indexes.grid_ind].can_be_collapsed {
-        if let Ok(transform) = transforms.get_mut(click.target) {
-            shuffle_tile_options(&mut grid[rect_indexes.grid_ind]);
-            let tile_opt: TileOption = find_proper_tile_option(grid, &grid[rect_indexes.grid_ind]);
-
-            commands.entity(click.target).remove::<Mesh2d>();
-            let spr_struct = spites_q.single();
-            let tile_option_index = match tile_opt {
-                TileOption::Blank => 0,
-                TileOption::Down => 1,
-                TileOption::Left => 2,
-                TileOption::Right => 3,
-                TileOption::Up => 4,
+    let rect_indexes: &RectangleIndexes = rect_indexes_q.get(click.target).unwrap();
+    let Ok(mut grid) = grid_q.get_single_mut() else {
+        text.0 = String::from("Still solving, plz wait");
+        return;
+    };
+    let solver: &mut Solver = &mut grid.0;
+
+    let clicked_tile: &Tile = &solver.grid.tiles[rect_indexes.grid_ind];
+    if let Some(explanation) = solver.contradiction() {
+        if explanation.cell == (clicked_tile.i, clicked_tile.j) {
+            println!("{explanation}");
+            text.0 = explanation_text(explanation);
+            solver.clear_contradiction();
+            return;
+        }
+    }
+    if clicked_tile.collapsed || !clicked_tile.can_be_collapsed {
+        text.0 = String::from("Can't collide this cell yet, plz click on green");
+        return;
+    }
+
+    println!("rect_index, {:?}", rect_indexes.grid_ind);
+
+    // a failed collapse may have undone other cells, so redraw either way
+    if collapse_cell(solver, rect_indexes.grid_ind, &mut text) {
+        mark_next_cells(solver, &mut text);
+    }
+
+    redraw_grid(
+        &mut commands,
+        &solver.grid,
+        spites_q.single(),
+        &rect_query,
+        &tile_sprite_query,
+        &mut materials,
+    );
+}
+
+// Collapses the cell and shows the outcome in the label, returns false if
+// the solver failed and the grid has to be restarted.
+fn collapse_cell(solver: &mut Solver, grid_ind: usize, text: &mut Text) -> bool {
+    match solver.collapse(grid_ind) {
+        Ok(Step::Collapsed(tile_opt)) => {
+            println!("collapsed to {:?}", tile_opt);
+            text.0 = String::from("Ok, this cell is collapsed");
+        }
+        Ok(Step::Backtracked) => {
+            text.0 = match solver.contradiction() {
+                Some(explanation) => format!(
+                    "Contradiction at {:?}, undid last decision ({} backtracks), click it to see why",
+                    explanation.cell, solver.backtracks
+                ),
+                None => format!(
+                    "Contradiction, undid last decision ({} backtracks)",
+                    solver.backtracks
+                ),
             };
-            let sprite: &Sprite = &spr_struct.0[tile_option_index];
-            commands.spawn((
-                sprite.clone(),
-                Transform::from_xyz(transform.translation.x, transform.translation.y, 0.),
-            ));
+        }
+        Ok(Step::Restarted) => {
+            text.0 = format!(
+                "Too many contradictions, started over ({} restarts)",
+                solver.restarts
+            );
+        }
+        Err(err) => {
+            text.0 = format!("ERROR: {err}, plz restart");
+            return false;
+        }
+    }
+    true
+}
 
-            println!("rect_index, {:?}", rect_indexes.grid_ind);
-
-            grid[rect_indexes.grid_ind].options = vec![tile_opt];
-            grid[rect_indexes.grid_ind].collapsed = true;
-
-            if !all_cell_collapsed(grid) {
-                update_near_cells_options(&mut grid, rect_indexes.i, rect_indexes.j);
-                println!("grid after update_near_cells_TileOptions {:?}", grid);
-
-                let indexes_2_collapse: Vec<usize> =
-                    find_and_mark_random_tile_with_low_entropy(&mut grid);
-                for elem in mat_query.iter_mut() {
-                    let some_mut: Mut<'_, MeshMaterial2d<ColorMaterial>> = elem.0;
-                    let asset_id: AssetId<ColorMaterial> = some_mut.0.id();
-                    if indexes_2_collapse.contains(&elem.1.grid_ind) {
-                        materials.get_mut(asset_id).unwrap().color = GREEN;
-                    } else {
-                        materials.get_mut(asset_id).unwrap().color = Color::BLACK;
-                    }
-                }
-                text.0 = String::from("Ok, this cell is collapsed");
-            } else {
-                text.0 = String::from("All cells are collapsed, game over");
-            }
+// The first steps of the explanation fit in the label, the whole chain is
+// printed to the console.
+fn explanation_text(explanation: &Explanation) -> String {
+    const SHOWN_STEPS: usize = 3;
+    let mut lines: Vec<String> = vec![format!("Cell {:?} had no options left:", explanation.cell)];
+    lines.extend(explanation.steps.iter().take(SHOWN_STEPS).cloned());
+    if explanation.steps.len() > SHOWN_STEPS {
+        lines.push(format!(
+            "... {} more in the console",
+            explanation.steps.len() - SHOWN_STEPS
+        ));
+    }
+    lines.join("\n")
+}
 
-            println!("grid after click {:?}", grid);
-        } else {
-            println!("Can't get transform");
+// Marks the cells which can be collapsed by hand next. A finished grid is
+// checked against the rules once more.
+fn mark_next_cells(solver: &mut Solver, text: &mut Text) {
+    if solver.is_done() {
+        let violations: Vec<Violation> = validate(&solver.grid, &solver.tileset);
+        for violation in &violations {
+            println!("rule violation: {violation}");
         }
-    } else {
-        text.0 = String::from("Can't collide this cell yet, plz click on green");
+        text.0 = match violations.first() {
+            None => String::from("All cells are collapsed, game over"),
+            Some(first) => format!(
+                "All cells are collapsed, but {} rule violations, first: {first}",
+                violations.len()
+            ),
+        };
+    } else if let Err(err) = solver.mark_lowest_entropy() {
+        text.0 = format!("ERROR: {err}, plz restart");
     }
 }
 
-fn button_system(
-    mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<Button>)>,
+// Collapses as many cells as the speed allows since the last frame while
+// playing, or one cell for a step. Stops at the end of the grid or on an
+// error.
+#[allow(clippy::too_many_arguments)]
+fn auto_collapse_system(
+    time: Res<Time>,
+    mut auto: ResMut<AutoMode>,
     mut commands: Commands,
-    sprite_query: Query<Entity, With<Sprite>>,
-    mesh_query: Query<Entity, With<Mesh2d>>,
+    spites_q: Query<&Sprites>,
+    mut grid_q: Query<&mut Grid>,
+    rect_query: Query<(
+        &MeshMaterial2d<ColorMaterial>,
+        &RectangleIndexes,
+        &Transform,
+    )>,
+    tile_sprite_query: Query<Entity, With<TileSprite>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
     mut text_query: Query<&mut Text, With<Label>>,
-    mut grid_query: Query<Entity, With<Grid>>,
-    meshes: ResMut<Assets<Mesh>>,
-    materials: ResMut<Assets<ColorMaterial>>,
-    mat_query: Query<Entity, With<MeshMaterial2d<ColorMaterial>>>,
 ) {
-    for interaction in &mut interaction_query {
-        match *interaction {
-            Interaction::Pressed => {
-                println!("Btn is pressed");
-                for entity in sprite_query.iter() {
-                    commands.entity(entity).remove::<Sprite>();
-                }
-
-                for entity in mesh_query.iter() {
-                    commands.entity(entity).remove::<Mesh2d>();
-                }
+    let cells: usize = if auto.playing {
+        // at most a second worth of cells after a slow frame, the budget
+        // changes every frame so it doesn't count as a change of the mode
+        let auto: &mut AutoMode = auto.bypass_change_detection();
+        auto.budget = (auto.budget + time.delta_secs() * auto.speed).min(auto.speed);
+        let cells: f32 = auto.budget.floor();
+        auto.budget -= cells;
+        cells as usize
+    } else if auto.step {
+        auto.step = false;
+        1
+    } else {
+        return;
+    };
+    if cells == 0 {
+        return;
+    }
+    let Ok(mut grid) = grid_q.get_single_mut() else {
+        return;
+    };
+    let solver: &mut Solver = &mut grid.0;
+    let mut text: Mut<'_, Text> = text_query.get_single_mut().unwrap();
 
-                text_query.get_single_mut().unwrap().0 = String::from("Plz click on some rect");
+    let mut failed: bool = false;
+    for _ in 0..cells {
+        if solver.is_done() {
+            break;
+        }
+        let collapsed: bool = match solver.next_cell() {
+            Ok(grid_ind) => collapse_cell(solver, grid_ind, &mut text),
+            Err(err) => {
+                text.0 = format!("ERROR: {err}, plz restart");
+                false
+            }
+        };
+        if !collapsed {
+            failed = true;
+            break;
+        }
+    }
+    if failed || solver.is_done() {
+        auto.playing = false;
+    }
+    if !failed {
+        mark_next_cells(solver, &mut text);
+    }
 
-                match grid_query.get_single_mut() {
-                    Ok(grid_entity) => {
-                        commands.entity(grid_entity).remove::<Grid>();
-                    }
-                    Err(_) => println!("No grid"),
-                }
+    redraw_grid(
+        &mut commands,
+        &solver.grid,
+        spites_q.single(),
+        &rect_query,
+        &tile_sprite_query,
+        &mut materials,
+    );
+}
 
-                for entity in mat_query.iter() {
-                    commands
-                        .entity(entity)
-                        .remove::<MeshMaterial2d<ColorMaterial>>();
-                }
+fn auto_button_system(
+    play_query: Query<&Interaction, (Changed<Interaction>, With<PlayButton>)>,
+    step_query: Query<&Interaction, (Changed<Interaction>, With<StepButton>)>,
+    mut auto: ResMut<AutoMode>,
+) {
+    if play_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        auto.playing = !auto.playing;
+    }
+    if step_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        auto.playing = false;
+        auto.step = true;
+    }
+}
 
-                println!("materials: {:?}", materials.len());
+// Undo and Redo buttons, or Ctrl+Z and Ctrl+Y. The grid, the sprites and
+// the highlighted cells are the same as before the undone decision.
+#[allow(clippy::too_many_arguments)]
+fn undo_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    undo_query: Query<&Interaction, (Changed<Interaction>, With<UndoButton>)>,
+    redo_query: Query<&Interaction, (Changed<Interaction>, With<RedoButton>)>,
+    mut auto: ResMut<AutoMode>,
+    mut commands: Commands,
+    spites_q: Query<&Sprites>,
+    mut grid_q: Query<&mut Grid>,
+    rect_query: Query<(
+        &MeshMaterial2d<ColorMaterial>,
+        &RectangleIndexes,
+        &Transform,
+    )>,
+    tile_sprite_query: Query<Entity, With<TileSprite>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut text_query: Query<&mut Text, With<Label>>,
+) {
+    let ctrl: bool = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let undo: bool = (ctrl && keys.just_pressed(KeyCode::KeyZ))
+        || undo_query
+            .iter()
+            .any(|interaction| *interaction == Interaction::Pressed);
+    let redo: bool = (ctrl && keys.just_pressed(KeyCode::KeyY))
+        || redo_query
+            .iter()
+            .any(|interaction| *interaction == Interaction::Pressed);
+    if !undo && !redo {
+        return;
+    }
+    // no Grid while a solve is running
+    let Ok(mut grid) = grid_q.get_single_mut() else {
+        return;
+    };
+    let solver: &mut Solver = &mut grid.0;
+    let mut text: Mut<'_, Text> = text_query.get_single_mut().unwrap();
+    auto.playing = false;
 
-                make_grid(commands, meshes, materials);
-                break;
-            }
-            _ => {}
+    if undo {
+        if !solver.undo() {
+            text.0 = String::from("Nothing to undo");
+            return;
         }
+        text.0 = String::from("Undid last decision");
+    } else {
+        if !solver.redo() {
+            text.0 = String::from("Nothing to redo");
+            return;
+        }
+        text.0 = String::from("Redid last decision");
     }
+    mark_next_cells(solver, &mut text);
+
+    redraw_grid(
+        &mut commands,
+        &solver.grid,
+        spites_q.single(),
+        &rect_query,
+        &tile_sprite_query,
+        &mut materials,
+    );
 }
 
-fn find_intesection(a: Vec<TileOption>, b: Vec<TileOption>) -> Vec<TileOption> {
-    let mut res: Vec<TileOption> = vec![];
-    for a_opt in a {
-        if b.contains(&a_opt) {
-            res.push(a_opt);
+// Moves the solver out of the Grid into a task which solves the rest of
+// the grid, so big grids don't block the frame.
+fn solve_button_system(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<SolveButton>)>,
+    grid_query: Query<Entity, With<Grid>>,
+    mut commands: Commands,
+    mut auto: ResMut<AutoMode>,
+    mut text_query: Query<&mut Text, With<Label>>,
+) {
+    if !interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        return;
+    }
+    // no Grid while a solve is running already
+    let Ok(grid_entity) = grid_query.get_single() else {
+        return;
+    };
+    auto.playing = false;
+    text_query.get_single_mut().unwrap().0 = String::from("Solving...");
+    commands.queue(move |world: &mut World| {
+        let mut entity: EntityWorldMut<'_> = world.entity_mut(grid_entity);
+        if let Some(Grid(solver)) = entity.take::<Grid>() {
+            entity.insert(start_solve(solver));
         }
+    });
+}
+
+fn start_solve(mut solver: Solver) -> SolveTask {
+    let (sender, receiver) = mpsc::channel::<SolveProgress>();
+    let cancel: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    let task_cancel: Arc<AtomicBool> = cancel.clone();
+    let cells: usize = solver.grid.tiles.len();
+    let task: Task<(Solver, Result<bool, WfcError>)> =
+        AsyncComputeTaskPool::get().spawn(async move {
+            let result: Result<bool, WfcError> = solver.run_with(|solver| {
+                // the receiver is gone once the task is dropped on restart
+                let _ = sender.send(SolveProgress {
+                    collapsed: solver.collapsed_count(),
+                    backtracks: solver.backtracks,
+                });
+                !task_cancel.load(Ordering::Relaxed)
+            });
+            (solver, result)
+        });
+    SolveTask {
+        task,
+        progress: Mutex::new(receiver),
+        cancel,
+        cells,
     }
-    res
 }
 
-fn get_possible_options(tile_opt: &TileOption, side: &str) -> Vec<TileOption> {
-    if side == "left" {
-        match tile_opt {
-            TileOption::Blank => vec![TileOption::Blank, TileOption::Left],
-            TileOption::Down => vec![TileOption::Down, TileOption::Right, TileOption::Up],
-            TileOption::Left => vec![TileOption::Down, TileOption::Right, TileOption::Up],
-            TileOption::Right => vec![TileOption::Blank, TileOption::Left],
-            TileOption::Up => vec![TileOption::Right, TileOption::Down, TileOption::Up],
-        }
-    } else if side == "right" {
-        match tile_opt {
-            TileOption::Blank => vec![TileOption::Blank, TileOption::Right],
-            TileOption::Down => vec![TileOption::Down, TileOption::Left, TileOption::Up],
-            TileOption::Left => vec![TileOption::Blank, TileOption::Right],
-            TileOption::Right => vec![TileOption::Down, TileOption::Left, TileOption::Up],
-            TileOption::Up => vec![TileOption::Left, TileOption::Down, TileOption::Up],
-        }
-    } else if side == "top" {
-        match tile_opt {
-            TileOption::Blank => vec![TileOption::Blank, TileOption::Up],
-            TileOption::Down => vec![TileOption::Blank, TileOption::Up],
-            TileOption::Left => {
-                vec![TileOption::Down, TileOption::Left, TileOption::Right]
-            }
-            TileOption::Right => {
-                vec![TileOption::Down, TileOption::Left, TileOption::Right]
-            }
-            TileOption::Up => vec![TileOption::Left, TileOption::Right, TileOption::Down],
+// Shows the latest progress of the solve and puts the solver back into a
+// Grid when the task is done.
+fn solve_poll_system(
+    mut commands: Commands,
+    mut solve_query: Query<(Entity, &mut SolveTask)>,
+    spites_q: Query<&Sprites>,
+    rect_query: Query<(
+        &MeshMaterial2d<ColorMaterial>,
+        &RectangleIndexes,
+        &Transform,
+    )>,
+    tile_sprite_query: Query<Entity, With<TileSprite>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut text_query: Query<&mut Text, With<Label>>,
+) {
+    let Ok((entity, mut solve)) = solve_query.get_single_mut() else {
+        return;
+    };
+    let mut text: Mut<'_, Text> = text_query.get_single_mut().unwrap();
+    let cells: usize = solve.cells;
+    if let Some(progress) = solve.progress.get_mut().unwrap().try_iter().last() {
+        text.0 = format!(
+            "Solving... {}% ({}/{} cells, {} backtracks)",
+            progress.collapsed * 100 / cells,
+            progress.collapsed,
+            cells,
+            progress.backtracks
+        );
+    }
+
+    let Some((mut solver, result)) = block_on(poll_once(&mut solve.task)) else {
+        return;
+    };
+    match result {
+        Ok(_) => mark_next_cells(&mut solver, &mut text),
+        Err(err) => text.0 = format!("ERROR: {err}, plz restart"),
+    }
+    redraw_grid(
+        &mut commands,
+        &solver.grid,
+        spites_q.single(),
+        &rect_query,
+        &tile_sprite_query,
+        &mut materials,
+    );
+    commands
+        .entity(entity)
+        .remove::<SolveTask>()
+        .insert(Grid(solver));
+}
+
+// The slider follows the cursor while the mouse button is held down on it.
+fn speed_slider_system(
+    slider_query: Query<(&Interaction, &RelativeCursorPosition), With<SpeedSlider>>,
+    mut auto: ResMut<AutoMode>,
+) {
+    for (interaction, cursor) in &slider_query {
+        if *interaction != Interaction::Pressed {
+            continue;
         }
-    } else if side == "btm" {
-        match tile_opt {
-            TileOption::Blank => vec![TileOption::Blank, TileOption::Down],
-            TileOption::Down => vec![TileOption::Left, TileOption::Right, TileOption::Up],
-            TileOption::Left => vec![TileOption::Left, TileOption::Right, TileOption::Up],
-            TileOption::Right => vec![TileOption::Left, TileOption::Right, TileOption::Up],
-            TileOption::Up => vec![TileOption::Blank, TileOption::Down],
+        let Some(position) = cursor.normalized else {
+            continue;
+        };
+        let speed: f32 = AutoMode::speed_at(position.x);
+        if speed != auto.speed {
+            auto.speed = speed;
         }
-    } else {
-        panic!("ERROR: no such side {side}");
     }
 }
 
-fn update_near_cells_options(
-    grid: &mut Vec<Tile>,
-    collapsed_cell_i: usize,
-    collapsed_cell_j: usize,
+fn auto_ui_system(
+    auto: Res<AutoMode>,
+    play_query: Query<&Children, With<PlayButton>>,
+    mut button_text_query: Query<&mut Text, Without<SpeedText>>,
+    mut speed_text_query: Query<&mut Text, With<SpeedText>>,
+    mut fill_query: Query<&mut Node, With<SpeedFill>>,
 ) {
-    for i in 0..DIM {
-        for j in 0..DIM {
-            if i == collapsed_cell_i && j == collapsed_cell_j {
-                let collapsed_cell: Tile = grid[i * DIM + j].clone();
-
-                let poss_left_i: i32 = i as i32;
-                let poss_left_j: i32 = j as i32 - 1;
-                if poss_left_j >= 0 {
-                    let left_ind: i32 = poss_left_i * (DIM as i32) + poss_left_j;
-                    let left_cell: &mut Tile = &mut grid[left_ind as usize];
-                    let new_left_cell_opt: Vec<TileOption> =
-                        get_possible_options(&collapsed_cell.options[0], "left");
-                    if !left_cell.collapsed {
-                        left_cell.options =
-                            find_intesection(left_cell.options.clone(), new_left_cell_opt);
-                        if left_cell.options.len() == 0 {
-                            panic!("No options for left cell");
-                        }
-                    }
-                }
-
-                let poss_right_i: i32 = i as i32;
-                let poss_right_j: i32 = j as i32 + 1;
-                if poss_right_j < DIM as i32 {
-                    let right_ind: i32 = poss_right_i * (DIM as i32) + poss_right_j;
-                    let right_cell: &mut Tile = &mut grid[right_ind as usize];
-                    let new_right_cell_opt: Vec<TileOption> =
-                        get_possible_options(&collapsed_cell.options[0], "right");
-                    if !right_cell.collapsed {
-                        right_cell.options =
-                            find_intesection(right_cell.options.clone(), new_right_cell_opt);
-                        if right_cell.options.len() == 0 {
-                            panic!("No options for right cell");
-                        }
-                    }
-                }
+    if !auto.is_changed() {
+        return;
+    }
+    for children in &play_query {
+        if let Ok(mut text) = button_text_query.get_mut(children[0]) {
+            text.0 = String::from(if auto.playing { "Pause" } else { "Play" });
+        }
+    }
+    for mut text in &mut speed_text_query {
+        text.0 = format!("{:.0} cells/s", auto.speed);
+    }
+    for mut node in &mut fill_query {
+        node.width = Val::Percent(auto.fraction() * 100.0);
+    }
+}
 
-                let poss_top_i: i32 = i as i32 - 1;
-                let poss_top_j: i32 = j as i32;
-                if poss_top_i >= 0 {
-                    let top_ind: i32 = poss_top_i * (DIM as i32) + poss_top_j;
-                    let new_top_cell_opt: Vec<TileOption> =
-                        get_possible_options(&collapsed_cell.options[0], "top");
-                    let top_cell: &mut Tile = &mut grid[top_ind as usize];
-                    if !top_cell.collapsed {
-                        top_cell.options =
-                            find_intesection(top_cell.options.clone(), new_top_cell_opt);
-                        if top_cell.options.len() == 0 {
-                            panic!("No options for top cell");
-                        }
-                    }
-                }
+// Respawns the sprites of collapsed cells and colors the rects of cells
+// which can be collapsed next, so the screen always matches the grid.
+fn redraw_grid(
+    commands: &mut Commands,
+    grid: &wfc_core::Grid,
+    sprites: &Sprites,
+    rect_query: &Query<(
+        &MeshMaterial2d<ColorMaterial>,
+        &RectangleIndexes,
+        &Transform,
+    )>,
+    tile_sprite_query: &Query<Entity, With<TileSprite>>,
+    materials: &mut Assets<ColorMaterial>,
+) {
+    for entity in tile_sprite_query.iter() {
+        commands.entity(entity).despawn();
+    }
 
-                let poss_btm_i: i32 = i as i32 + 1;
-                let poss_btm_j: i32 = j as i32;
-                if poss_btm_i < DIM as i32 {
-                    let bottom_ind: i32 = poss_btm_i * (DIM as i32) + poss_btm_j;
-                    let new_bottom_cell_opt: Vec<TileOption> =
-                        get_possible_options(&collapsed_cell.options[0], "btm");
-                    let bottom_cell: &mut Tile = &mut grid[bottom_ind as usize];
-                    if !bottom_cell.collapsed {
-                        bottom_cell.options =
-                            find_intesection(bottom_cell.options.clone(), new_bottom_cell_opt);
-                        if bottom_cell.options.len() == 0 {
-                            panic!("No options for bottom cell");
-                        }
-                    }
-                }
-            }
+    for (material, rect_indexes, transform) in rect_query.iter() {
+        let tile: &Tile = &grid.tiles[rect_indexes.grid_ind];
+        if tile.collapsed {
+            spawn_tile_sprite(commands, sprites, tile, transform);
         }
+        materials.get_mut(material.0.id()).unwrap().color = cell_color(tile);
     }
 }
 
-fn all_cell_collapsed(grid: &Vec<Tile>) -> bool {
-    for cell in grid {
-        if !cell.collapsed {
-            return false;
-        }
+// green for cells which can be collapsed next
+fn cell_color(tile: &Tile) -> Color {
+    if !tile.collapsed && tile.can_be_collapsed {
+        GREEN
+    } else {
+        Color::BLACK
     }
-    return true;
 }
 
-fn find_and_mark_random_tile_with_low_entropy(grid: &mut Vec<Tile>) -> Vec<usize> {
-    let mut lowest_entropy: usize = usize::MAX;
-    for i in 0..DIM {
-        for j in 0..DIM {
-            let grid_ind: usize = i * DIM + j;
-            let grid_cell: &Tile = &grid[grid_ind];
-            if !grid_cell.collapsed {
-                if grid_cell.options.len() < lowest_entropy {
-                    lowest_entropy = grid_cell.options.len();
-                }
-            }
+// sprite of a collapsed tile on top of its rect
+fn spawn_tile_sprite(commands: &mut Commands, sprites: &Sprites, tile: &Tile, rect: &Transform) {
+    let Some(tile_opt) = tile.options.first() else {
+        return;
+    };
+    let (sprite, rotation): &(Sprite, Quat) = &sprites.0[tile_opt.index()];
+    commands.spawn((
+        sprite.clone(),
+        Transform::from_xyz(rect.translation.x, rect.translation.y, 1.).with_rotation(*rotation),
+        TileSprite,
+    ));
+}
+
+fn button_system(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<RestartButton>)>,
+    config: Res<GridConfig>,
+    mut replace_writer: EventWriter<ReplaceGrid>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            println!("Btn is pressed");
+            replace_writer.send(ReplaceGrid {
+                grid: wfc_core::Grid::new(
+                    config.width,
+                    config.height,
+                    pick_seed(),
+                    &config.tileset,
+                ),
+                message: String::from("Plz click on some rect"),
+            });
+            break;
         }
     }
+}
 
-    let mut indexes_2_collapse: Vec<usize> = vec![];
-    for i in 0..DIM {
-        for j in 0..DIM {
-            let grid_ind: usize = i * DIM + j;
-            let grid_cell: &mut Tile = &mut grid[grid_ind];
-            if !grid_cell.collapsed && grid_cell.options.len() == lowest_entropy {
-                indexes_2_collapse.push(grid_ind);
-                grid_cell.can_be_collapsed = true;
-            } else if !grid_cell.collapsed {
-                grid_cell.can_be_collapsed = false;
+// Set WFC_SAVE to the file Save writes and Load reads.
+fn save_load_system(
+    save_query: Query<&Interaction, (Changed<Interaction>, With<SaveButton>)>,
+    load_query: Query<&Interaction, (Changed<Interaction>, With<LoadButton>)>,
+    grid_q: Query<&Grid>,
+    config: Res<GridConfig>,
+    mut text_query: Query<&mut Text, With<Label>>,
+    mut replace_writer: EventWriter<ReplaceGrid>,
+) {
+    let mut text: Mut<'_, Text> = text_query.get_single_mut().unwrap();
+    if save_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        text.0 = match grid_q.get_single() {
+            Ok(grid) => match grid.0.grid.save(&config.save_path, &grid.0.tileset) {
+                Ok(()) => format!("Saved to {}", config.save_path),
+                Err(err) => format!("ERROR: {err}"),
+            },
+            Err(_) => String::from("Still solving, plz wait"),
+        };
+    }
+    if load_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        match wfc_core::Grid::load(&config.save_path, &config.tileset) {
+            Ok(grid) => {
+                replace_writer.send(ReplaceGrid {
+                    grid,
+                    message: format!("Loaded {}", config.save_path),
+                });
             }
+            Err(err) => text.0 = format!("ERROR: {err}"),
         }
     }
+}
 
-    if indexes_2_collapse.len() <= 0 {
-        panic!("ERROR: indexes_2_collapse is empty, grid is {:?}", grid);
+// Writes the collapsed cells as a PNG, drawn on the CPU with the same tile
+// size and gap as on screen.
+fn export_system(
+    export_query: Query<&Interaction, (Changed<Interaction>, With<ExportButton>)>,
+    grid_q: Query<&Grid>,
+    config: Res<GridConfig>,
+    mut text_query: Query<&mut Text, With<Label>>,
+) {
+    if !export_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        return;
     }
-    indexes_2_collapse
+    let Ok(grid) = grid_q.get_single() else {
+        text_query.get_single_mut().unwrap().0 = String::from("Still solving, plz wait");
+        return;
+    };
+    let options: RenderOptions = RenderOptions {
+        tile_size: SPRITE_SIZE as u32,
+        gap: SPRITE_GAP as u32,
+        ..default()
+    };
+    text_query.get_single_mut().unwrap().0 = match export_png(
+        &grid.0.grid,
+        &config.tileset,
+        "assets",
+        &options,
+        &config.export_path,
+    ) {
+        Ok(()) => format!("Exported to {}", config.export_path),
+        Err(err) => format!("ERROR: {err}"),
+    };
 }
 
-fn check_side(
-    grid: &Vec<Tile>,
-    tile_to_collapse_i: usize,
-    tile_to_collapse_j: usize,
-    tile_to_collapse_opt: &TileOption,
-    side: &str,
-) -> bool {
-    let mut poss_side_i: i32 = 0;
-    let mut poss_side_j: i32 = 0;
-    let mut ind_to_change: i32 = 0;
-    let mut how_to_change: i32 = 0;
+// Throws away the grid on screen, cancels a running solve and shows the new
+// grid.
+#[allow(clippy::too_many_arguments)]
+fn replace_grid_system(
+    mut replace_reader: EventReader<ReplaceGrid>,
+    mut commands: Commands,
+    sprite_query: Query<Entity, With<Sprite>>,
+    mesh_query: Query<Entity, With<Mesh2d>>,
+    mut text_query: Query<&mut Text, With<Label>>,
+    mut seed_text_query: Query<&mut Text, (With<SeedText>, Without<Label>)>,
+    mut grid_query: Query<Entity, With<Grid>>,
+    solve_query: Query<(Entity, &SolveTask)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mat_query: Query<Entity, With<MeshMaterial2d<ColorMaterial>>>,
+    config: Res<GridConfig>,
+    mut auto: ResMut<AutoMode>,
+    spites_q: Query<&Sprites>,
+    mut camera_query: Query<&mut OrthographicProjection>,
+) {
+    let Some(replace) = replace_reader.read().last() else {
+        return;
+    };
+    auto.playing = false;
+    for entity in sprite_query.iter() {
+        commands.entity(entity).remove::<Sprite>();
+    }
 
-    let mut how_to_change_i: i32 = 0;
-    let mut how_to_change_j: i32 = 0;
+    for entity in mesh_query.iter() {
+        commands.entity(entity).remove::<Mesh2d>();
+    }
 
-    match side {
-        "left" => {
-            poss_side_i = tile_to_collapse_i as i32;
-            poss_side_j = tile_to_collapse_j as i32 - 1;
-            ind_to_change = poss_side_j;
-            how_to_change = -1;
+    text_query.get_single_mut().unwrap().0 = replace.message.clone();
 
-            how_to_change_i = 0;
-            how_to_change_j = -1;
-        }
-        "right" => {
-            poss_side_i = tile_to_collapse_i as i32;
-            poss_side_j = tile_to_collapse_j as i32 + 1;
-            ind_to_change = poss_side_j;
-            how_to_change = 1;
-
-            how_to_change_i = 0;
-            how_to_change_j = 1;
-        }
-        "top" => {
-            poss_side_i = tile_to_collapse_i as i32 - 1;
-            poss_side_j = tile_to_collapse_j as i32;
-            ind_to_change = poss_side_i;
-            how_to_change = -1;
-
-            how_to_change_i = -1;
-            how_to_change_j = 0;
-        }
-        "btm" => {
-            poss_side_i = tile_to_collapse_i as i32 + 1;
-            poss_side_j = tile_to_collapse_j as i32;
-            ind_to_change = poss_side_i;
-            how_to_change = 1;
-
-            how_to_change_i = 1;
-            how_to_change_j = 0;
-        }
-        _ => {
-            panic!("Unknown side: {side}")
+    match grid_query.get_single_mut() {
+        Ok(grid_entity) => {
+            commands.entity(grid_entity).remove::<Grid>();
         }
+        Err(_) => println!("No grid"),
     }
 
-    let mut side_is_ok: bool = false;
-
-    if ind_to_change >= 0 && ind_to_change < DIM as i32 {
-        let mut current_tile_opt: &TileOption = tile_to_collapse_opt;
-        let mut intersect: Vec<TileOption> = vec![];
-        while ind_to_change >= 0 && ind_to_change < DIM as i32 {
-            let side_ind: i32 = poss_side_i * (DIM as i32) + poss_side_j;
-            let side_tile: &Tile = &grid[side_ind as usize];
-            let side_possible_opts: Vec<TileOption> = get_possible_options(current_tile_opt, side);
-            intersect = find_intesection(side_tile.options.clone(), side_possible_opts);
-            if intersect.len() == 0 {
-                side_is_ok = false;
-                break;
-            } else if intersect.len() == 1 {
-                current_tile_opt = &intersect[0];
-                side_is_ok = true;
-            } else {
-                side_is_ok = true;
-                break;
-            }
-            ind_to_change += how_to_change;
-
-            poss_side_i += how_to_change_i;
-            poss_side_j += how_to_change_j;
-        }
-    } else {
-        side_is_ok = true;
-    }
-    side_is_ok
-}
-
-fn find_proper_tile_option(grid: &Vec<Tile>, tile_to_collapse: &Tile) -> TileOption {
-    let mut opt_to_collapse: Option<TileOption> = None;
-
-    // check opt for possible collapse
-    for i in 0..DIM {
-        for j in 0..DIM {
-            if i == tile_to_collapse.i && j == tile_to_collapse.j {
-                for tile_to_collapse_opt in &tile_to_collapse.options {
-                    let mut left_side_is_ok: bool = false;
-                    let mut right_side_is_ok: bool = false;
-                    let mut top_side_is_ok: bool = false;
-                    let mut btm_side_is_ok: bool = false;
-
-                    // go to the left and check all left cells:
-                    left_side_is_ok = check_side(
-                        &grid,
-                        tile_to_collapse.i,
-                        tile_to_collapse.j,
-                        tile_to_collapse_opt,
-                        "left",
-                    );
-                    if !left_side_is_ok {
-                        continue;
-                    }
-
-                    // go to the right and check all right cells:
-                    right_side_is_ok = check_side(
-                        &grid,
-                        tile_to_collapse.i,
-                        tile_to_collapse.j,
-                        tile_to_collapse_opt,
-                        "right",
-                    );
-                    if !right_side_is_ok {
-                        continue;
-                    }
-
-                    // go to the top and check all top cells:
-                    top_side_is_ok = check_side(
-                        &grid,
-                        tile_to_collapse.i,
-                        tile_to_collapse.j,
-                        tile_to_collapse_opt,
-                        "top",
-                    );
-                    if !top_side_is_ok {
-                        continue;
-                    }
-
-                    // go to the btm and check all btm cells:
-                    btm_side_is_ok = check_side(
-                        &grid,
-                        tile_to_collapse.i,
-                        tile_to_collapse.j,
-                        tile_to_collapse_opt,
-                        "btm",
-                    );
-                    if !btm_side_is_ok {
-                        continue;
-                    }
-
-                    if left_side_is_ok && right_side_is_ok && top_side_is_ok && btm_side_is_ok {
-                        opt_to_collapse = Some(tile_to_collapse_opt.clone());
-                        return opt_to_collapse.unwrap();
-                    }
-                }
-            }
-        }
+    for (entity, solve) in solve_query.iter() {
+        solve.cancel.store(true, Ordering::Relaxed);
+        commands.entity(entity).remove::<SolveTask>();
     }
-    if opt_to_collapse == None {
-        panic!(
-            "ERROR: cant find TileOption to collapse tile {:?}",
-            tile_to_collapse
-        );
-    } else {
-        panic!(
-            "ERROR: found opt_to_collapse {:?}, but not return? WTF?",
-            tile_to_collapse
-        );
+
+    for entity in mat_query.iter() {
+        commands
+            .entity(entity)
+            .remove::<MeshMaterial2d<ColorMaterial>>();
     }
-}
 
-fn shuffle_tile_options(tile: &mut Tile) {
-    if tile.options.len() <= 0 {
-        panic!("ERROR: tile {:?} has zero TileOptions", tile);
+    println!("materials: {:?}", materials.len());
+
+    seed_text_query.get_single_mut().unwrap().0 = format!("Seed: {}", replace.grid.seed);
+    for mut projection in &mut camera_query {
+        projection.scale = camera_scale(replace.grid.width, replace.grid.height);
     }
-    let mut rng: ThreadRng = rand::rng();
-    tile.options.shuffle(&mut rng);
+
+    make_grid(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &config,
+        spites_q.single(),
+        replace.grid.clone(),
+    );
 }
@@ -0,0 +1,193 @@
+use std::fmt;
+
+use crate::grid::SIDES;
+use crate::options::OptionSet;
+use crate::tile::TileOption;
+use crate::tileset::{Tileset, OPPOSITE};
+
+// Problems found in the adjacency rules of a tileset, tiles are given by
+// name and sides by their name in SIDES.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RuleReport {
+    // (tile, side, neighbour): tile allows neighbour on that side, but the
+    // neighbour doesn't allow tile on the opposite side
+    pub asymmetric: Vec<(String, &'static str, String)>,
+    // (tile, side): no tile can sit on that side of the tile
+    pub dead_ends: Vec<(String, &'static str)>,
+    // tiles which can't be part of any finished grid
+    pub never_appear: Vec<String>,
+    // (tile, side): the tile can only sit on that border of the grid, as
+    // nothing which can appear fits on that side of it
+    pub border_only: Vec<(String, &'static str)>,
+}
+
+impl RuleReport {
+    pub fn is_empty(&self) -> bool {
+        self.asymmetric.is_empty()
+            && self.dead_ends.is_empty()
+            && self.never_appear.is_empty()
+            && self.border_only.is_empty()
+    }
+}
+
+impl fmt::Display for RuleReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (tile, side, neighbour) in &self.asymmetric {
+            let opposite: &str = SIDES[OPPOSITE[SIDES.iter().position(|s| s == side).unwrap()]];
+            writeln!(
+                f,
+                "asymmetric: {tile} allows {neighbour} on its {side} side, \
+                 but {neighbour} doesn't allow {tile} on its {opposite} side"
+            )?;
+        }
+        for (tile, side) in &self.dead_ends {
+            writeln!(f, "dead end: nothing fits on the {side} side of {tile}")?;
+        }
+        for tile in &self.never_appear {
+            writeln!(f, "never appears: {tile}")?;
+        }
+        for (tile, side) in &self.border_only {
+            writeln!(
+                f,
+                "border only: {tile} can only appear on the {side} border"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Checks the rules of the tileset. The solver only keeps a pair of
+// neighbours which both tiles allow, so an asymmetric rule acts as if it
+// wasn't there, dead ends and tiles which never appear are found with that
+// in mind. A tile with nothing to fit on one side can still sit on that
+// border of the grid, it only never appears if nothing fits on two opposite
+// sides, or if all it fits next to there are such tiles. This assumes grids
+// of at least 2x2 cells.
+pub fn analyze_rules(tileset: &Tileset) -> RuleReport {
+    let name = |tile_opt: TileOption| -> String { tileset.tiles[tile_opt.index()].name.clone() };
+    let mut report: RuleReport = RuleReport::default();
+
+    // allowed[tile][side] are the neighbours both tiles agree on
+    let mut allowed: Vec<[OptionSet; 4]> = vec![[OptionSet::empty(); 4]; tileset.len()];
+    for (tile_ind, tile_allowed) in allowed.iter_mut().enumerate() {
+        let tile_opt: TileOption = TileOption(tile_ind);
+        for (side_ind, side) in SIDES.iter().enumerate() {
+            for other in tileset.compatible(&tile_opt, side_ind).iter() {
+                if tileset
+                    .compatible(&other, OPPOSITE[side_ind])
+                    .contains(tile_opt)
+                {
+                    tile_allowed[side_ind].insert(other);
+                } else {
+                    report.asymmetric.push((name(tile_opt), side, name(other)));
+                }
+            }
+            if tile_allowed[side_ind].is_empty() {
+                report.dead_ends.push((name(tile_opt), side));
+            }
+        }
+    }
+
+    // drop tiles with nothing left on two opposite sides until nothing
+    // changes
+    let mut alive: OptionSet = tileset.all_options();
+    let fits = |tile_opt: TileOption, side_ind: usize, alive: OptionSet| -> bool {
+        !(allowed[tile_opt.index()][side_ind] & alive).is_empty()
+    };
+    loop {
+        let dead: OptionSet = alive
+            .iter()
+            .filter(|tile_opt| {
+                [(0, 1), (2, 3)]
+                    .iter()
+                    .any(|&(a, b)| !fits(*tile_opt, a, alive) && !fits(*tile_opt, b, alive))
+            })
+            .collect();
+        if dead.is_empty() {
+            break;
+        }
+        for tile_opt in dead.iter() {
+            alive.remove(tile_opt);
+        }
+    }
+    report.never_appear = tileset
+        .all_options()
+        .iter()
+        .filter(|tile_opt| !alive.contains(*tile_opt))
+        .map(name)
+        .collect();
+    for tile_opt in alive.iter() {
+        for (side_ind, side) in SIDES.iter().enumerate() {
+            if !fits(tile_opt, side_ind, alive) {
+                report.border_only.push((name(tile_opt), side));
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(manifest: &str) -> RuleReport {
+        analyze_rules(&Tileset::from_ron(manifest).unwrap())
+    }
+
+    #[test]
+    fn default_tileset_is_clean() {
+        assert!(report(include_str!("../../assets/tiles.ron")).is_empty());
+    }
+
+    #[test]
+    fn finds_asymmetric_pairs_and_dead_ends() {
+        let report: RuleReport = report(
+            r#"(
+                name: "bad",
+                tiles: [
+                    (name: "a", image: "a.png", left: ["a", "b"], right: ["a"], top: ["a", "c"], btm: ["a", "c"]),
+                    (name: "b", image: "b.png", left: ["a"], right: ["a"], top: ["b"], btm: ["b"]),
+                    (name: "c", image: "c.png", left: [], right: ["c"], top: ["a"], btm: ["a"]),
+                ],
+            )"#,
+        );
+        assert_eq!(
+            report.asymmetric,
+            vec![
+                (String::from("b"), "left", String::from("a")),
+                (String::from("c"), "right", String::from("c")),
+            ]
+        );
+        assert_eq!(
+            report.dead_ends,
+            vec![
+                (String::from("b"), "left"),
+                (String::from("c"), "left"),
+                (String::from("c"), "right"),
+            ]
+        );
+        // c has nothing on both horizontal sides
+        assert_eq!(report.never_appear, vec![String::from("c")]);
+        assert_eq!(report.border_only, vec![(String::from("b"), "left")]);
+    }
+
+    #[test]
+    fn one_dead_side_is_border_only() {
+        let report: RuleReport = report(
+            r#"(
+                name: "two",
+                tiles: [
+                    (name: "a", image: "a.png", left: ["a", "b"], right: ["a"], top: ["a", "b"], btm: ["a", "b"]),
+                    (name: "b", image: "b.png", left: [], right: ["a"], top: ["a", "b"], btm: ["a", "b"]),
+                ],
+            )"#,
+        );
+        assert!(report.never_appear.is_empty());
+        assert_eq!(report.border_only, vec![(String::from("b"), "left")]);
+        assert_eq!(
+            report.to_string(),
+            "dead end: nothing fits on the left side of b\n\
+             border only: b can only appear on the left border\n"
+        );
+    }
+}
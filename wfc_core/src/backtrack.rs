@@ -0,0 +1,647 @@
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::error::WfcError;
+use crate::explain::{explain, Explanation};
+use crate::grid::Grid;
+use crate::options::OptionSet;
+use crate::queue::EntropyQueue;
+use crate::select::{CellSelector, MinEntropy, ValueSelector, WeightedRandom};
+use crate::solver::{find_proper_tile_option, Cause, Change, Propagator, ENTROPY_EPSILON};
+use crate::tile::{Tile, TileOption};
+use crate::tileset::Tileset;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Collapsed(TileOption),
+    Backtracked,
+    // too many backtracks in a row, the grid is back to how it was when
+    // the solver was made
+    Restarted,
+}
+
+// Backtracks in the first attempt before the solver starts over, every
+// restart doubles it. Backtracking can't get out of a dead end made by a
+// decision long ago, starting over with other random choices usually
+// does, and the longer attempts still solve grids which need more
+// backtracks than the first one allows.
+pub const RESTART_AFTER: usize = 100;
+
+// A collapsed cell, trail_len is the length of the trail right before it,
+// undoing the trail down to it restores the grid as it was.
+#[derive(Debug, Clone)]
+struct Decision {
+    grid_ind: usize,
+    tile_opt: TileOption,
+    trail_len: usize,
+}
+
+// An undone decision with the changes it made, as (before, after) pairs in
+// trail order, so redo gives exactly the same grid again.
+#[derive(Debug, Clone)]
+struct Undone {
+    decision: Decision,
+    changes: Vec<(Change, Change)>,
+}
+
+#[derive(Debug)]
+pub struct Solver {
+    pub grid: Grid,
+    pub tileset: Tileset,
+    // most backtracks in one attempt, the solver gives up once an attempt
+    // needs more
+    pub max_backtracks: usize,
+    pub backtracks: usize,
+    pub restart_after: usize,
+    pub restarts: usize,
+    // backtracks since the last restart
+    attempt_backtracks: usize,
+    decisions: Vec<Decision>,
+    trail: Vec<Change>,
+    propagator: Propagator,
+    // for every ban the decisions it depends on, as indexes into decisions
+    ban_reasons: Vec<Vec<usize>>,
+    undone: Vec<Undone>,
+    collapsed_count: usize,
+    cell_selector: Box<dyn CellSelector>,
+    value_selector: Box<dyn ValueSelector>,
+    // built by the first mark_lowest_entropy call
+    marks: Option<EntropyQueue>,
+    marked: Vec<usize>,
+    // why the last contradiction happened
+    contradiction: Option<Explanation>,
+    rng: StdRng,
+}
+
+impl Solver {
+    // The RNG is seeded from grid.seed, so the same seed and the same
+    // sequence of collapsed cells always give the same grid.
+    pub fn new(grid: Grid, tileset: Tileset, max_backtracks: usize) -> Solver {
+        let rng: StdRng = StdRng::seed_from_u64(grid.seed);
+        let collapsed_count: usize = grid.tiles.iter().filter(|tile| tile.collapsed).count();
+        Solver {
+            grid,
+            tileset,
+            max_backtracks,
+            backtracks: 0,
+            restart_after: RESTART_AFTER,
+            restarts: 0,
+            attempt_backtracks: 0,
+            decisions: vec![],
+            trail: vec![],
+            propagator: Propagator::default(),
+            ban_reasons: vec![],
+            undone: vec![],
+            collapsed_count,
+            cell_selector: Box::new(MinEntropy::default()),
+            value_selector: Box::new(WeightedRandom),
+            marks: None,
+            marked: vec![],
+            contradiction: None,
+            rng,
+        }
+    }
+
+    // Cells are picked by lowest entropy unless another strategy is given.
+    pub fn with_cell_selector(mut self, cell_selector: Box<dyn CellSelector>) -> Solver {
+        self.cell_selector = cell_selector;
+        self
+    }
+
+    // Options are tried in weighted random order unless another strategy is
+    // given.
+    pub fn with_value_selector(mut self, value_selector: Box<dyn ValueSelector>) -> Solver {
+        self.value_selector = value_selector;
+        self
+    }
+
+    // Collapses the tile at grid_ind and propagates the change. On a
+    // contradiction the last decisions are undone until the grid is
+    // consistent again, so the tile may stay uncollapsed.
+    pub fn collapse(&mut self, grid_ind: usize) -> Result<Step, WfcError> {
+        let Some(tile) = self.grid.tiles.get(grid_ind) else {
+            return Err(WfcError::CellOutOfRange { grid_ind });
+        };
+        if tile.collapsed {
+            return Err(WfcError::AlreadyCollapsed {
+                cell: (tile.i, tile.j),
+            });
+        }
+        if tile.options.is_empty() {
+            return Err(WfcError::EmptyOptions {
+                cell: (tile.i, tile.j),
+            });
+        }
+        self.undone.clear();
+        let order: Vec<TileOption> =
+            self.value_selector
+                .order(&self.grid, &self.tileset, grid_ind, &mut self.rng)?;
+        let tile_opt: TileOption = match find_proper_tile_option(
+            &self.grid,
+            &self.tileset,
+            &self.grid.tiles[grid_ind],
+            &order,
+        ) {
+            Ok(tile_opt) => tile_opt,
+            Err(err @ WfcError::NoValidOption { .. }) => return self.backtrack(err),
+            Err(err) => return Err(err),
+        };
+
+        let trail_len: usize = self.trail.len();
+        self.trail
+            .push(Change::of(&self.grid, grid_ind, Cause::Decision));
+        self.grid.tiles[grid_ind].options = OptionSet::single(tile_opt);
+        self.grid.tiles[grid_ind].collapsed = true;
+        self.collapsed_count += 1;
+        self.decisions.push(Decision {
+            grid_ind,
+            tile_opt,
+            trail_len,
+        });
+
+        let propagated: Result<(), WfcError> =
+            self.propagator
+                .propagate(&mut self.grid, &self.tileset, grid_ind, &mut self.trail);
+        self.cells_changed(trail_len);
+        match propagated {
+            Ok(()) => Ok(Step::Collapsed(tile_opt)),
+            Err(err @ WfcError::Contradiction { .. }) => {
+                self.explain_contradiction(&err);
+                self.backtrack(err)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    // Conflict-directed backjumping: finds the decisions the failure depends
+    // on, undoes everything back to the latest of them and bans the option
+    // it chose. Decisions in between had nothing to do with the failure, so
+    // they are undone without a ban. Repeats while the ban itself leads to
+    // a contradiction. Returns the failure if it depends on no decision at
+    // all. On an error the failed step is undone too, so the grid is never
+    // left half propagated.
+    fn backtrack(&mut self, mut err: WfcError) -> Result<Step, WfcError> {
+        // nothing was changed if no option passed the look-ahead
+        let mut consistent: bool = matches!(err, WfcError::NoValidOption { .. });
+        let mut conflict: Vec<usize> = self.conflict(&err);
+        loop {
+            let Some(target) = conflict.pop() else {
+                // no decision to take back, so the grid can't be solved from
+                // where the solver started and goes back there
+                if !consistent {
+                    self.decisions.clear();
+                    self.undone.clear();
+                    self.undo_trail(0);
+                }
+                return Err(err);
+            };
+            let restart_after: usize = self.restart_interval();
+            if self.attempt_backtracks >= restart_after && restart_after < self.max_backtracks {
+                self.restart();
+                return Ok(Step::Restarted);
+            }
+            if self.attempt_backtracks >= self.max_backtracks {
+                if !consistent {
+                    let trail_len: usize = self.decisions.pop().map_or(0, |d| d.trail_len);
+                    self.undo_trail(trail_len);
+                }
+                return Err(WfcError::BacktrackLimit {
+                    backtracks: self.backtracks,
+                });
+            }
+            self.decisions.truncate(target + 1);
+            let decision: Decision = self.decisions.pop().unwrap();
+            consistent = false;
+            self.backtracks += 1;
+            self.attempt_backtracks += 1;
+            self.undo_trail(decision.trail_len);
+
+            // the ban holds as long as the rest of the conflict does
+            let trail_len: usize = self.trail.len();
+            let reason: usize = self.ban_reasons.len();
+            self.ban_reasons.push(conflict);
+            self.trail.push(Change::of(
+                &self.grid,
+                decision.grid_ind,
+                Cause::Ban(reason),
+            ));
+            let tile: &mut Tile = &mut self.grid.tiles[decision.grid_ind];
+            tile.options.remove(decision.tile_opt);
+            if tile.options.is_empty() {
+                err = WfcError::EmptyOptions {
+                    cell: (tile.i, tile.j),
+                };
+                self.cells_changed(trail_len);
+                conflict = self.conflict(&err);
+                continue;
+            }
+            let propagated: Result<(), WfcError> = self.propagator.propagate(
+                &mut self.grid,
+                &self.tileset,
+                decision.grid_ind,
+                &mut self.trail,
+            );
+            self.cells_changed(trail_len);
+            match propagated {
+                Ok(()) => return Ok(Step::Backtracked),
+                Err(propagate_err @ WfcError::Contradiction { .. }) => {
+                    self.explain_contradiction(&propagate_err);
+                    conflict = self.conflict(&propagate_err);
+                    err = propagate_err;
+                }
+                Err(propagate_err) => return Err(propagate_err),
+            }
+        }
+    }
+
+    // Backtracks allowed in the current attempt before starting over.
+    fn restart_interval(&self) -> usize {
+        let doublings: u32 = self.restarts.try_into().unwrap_or(u32::MAX);
+        self.restart_after
+            .saturating_mul(2usize.saturating_pow(doublings))
+    }
+
+    // Undoes every decision, the random choices of the next attempt differ
+    // as the RNG isn't reset.
+    fn restart(&mut self) {
+        self.restarts += 1;
+        self.attempt_backtracks = 0;
+        self.decisions.clear();
+        self.undone.clear();
+        self.undo_trail(0);
+    }
+
+    // Indexes of the decisions a failure depends on, in increasing order. A
+    // cell which ran out of options depends on everything which removed
+    // them: the neighbours they were removed for, and so on back to the
+    // decisions and bans. Why no option passed the look-ahead isn't
+    // recorded, so that depends on all decisions.
+    fn conflict(&self, err: &WfcError) -> Vec<usize> {
+        let (WfcError::Contradiction { cell, .. } | WfcError::EmptyOptions { cell }) = err else {
+            return (0..self.decisions.len()).collect();
+        };
+        // cells whose options at the point of the trail matter
+        let mut active: Vec<bool> = vec![false; self.grid.tiles.len()];
+        active[self.grid.index(cell.0, cell.1)] = true;
+        let mut conflict: Vec<usize> = vec![];
+        for (trail_ind, change) in self.trail.iter().enumerate().rev() {
+            if !active[change.grid_ind] {
+                continue;
+            }
+            match change.cause {
+                Cause::Decision => {
+                    // the cell has the chosen option no matter what it lost
+                    // before
+                    active[change.grid_ind] = false;
+                    let decision_ind: usize = self
+                        .decisions
+                        .partition_point(|decision| decision.trail_len <= trail_ind)
+                        - 1;
+                    conflict.push(decision_ind);
+                }
+                Cause::Neighbour(source) => active[source] = true,
+                Cause::Ban(reason) => conflict.extend(&self.ban_reasons[reason]),
+            }
+        }
+        conflict.sort_unstable();
+        conflict.dedup();
+        conflict
+    }
+
+    // Keeps the explanation of a contradiction, it has to be made before
+    // the trail is undone.
+    fn explain_contradiction(&mut self, err: &WfcError) {
+        if let WfcError::Contradiction { cell, .. } = err {
+            let failed_ind: usize = self.grid.index(cell.0, cell.1);
+            self.contradiction = Some(explain(&self.grid, &self.tileset, &self.trail, failed_ind));
+        }
+    }
+
+    // Why the last contradiction happened, which cell lost its last option
+    // and the chain of removed options leading to it. Kept until the next
+    // contradiction or clear_contradiction.
+    pub fn contradiction(&self) -> Option<&Explanation> {
+        self.contradiction.as_ref()
+    }
+
+    pub fn clear_contradiction(&mut self) {
+        self.contradiction = None;
+    }
+
+    // Undoes the trail down to trail_len changes, returns the undone changes
+    // as (before, after) pairs in trail order.
+    fn undo_trail(&mut self, trail_len: usize) -> Vec<(Change, Change)> {
+        let mut undone: Vec<(Change, Change)> = vec![];
+        while self.trail.len() > trail_len {
+            let before: Change = self.trail.pop().unwrap();
+            undone.push((
+                before,
+                Change::of(&self.grid, before.grid_ind, before.cause),
+            ));
+            self.set_tile(&before);
+        }
+        undone.reverse();
+        let changed: Vec<usize> = undone.iter().map(|(before, _)| before.grid_ind).collect();
+        self.notify(&changed);
+        undone
+    }
+
+    fn set_tile(&mut self, state: &Change) {
+        if self.grid.tiles[state.grid_ind].collapsed != state.collapsed {
+            if state.collapsed {
+                self.collapsed_count += 1;
+            } else {
+                self.collapsed_count -= 1;
+            }
+        }
+        state.restore(&mut self.grid);
+    }
+
+    // Undoes the last collapse decision together with all the options it
+    // removed. Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(decision) = self.decisions.pop() else {
+            return false;
+        };
+        let changes: Vec<(Change, Change)> = self.undo_trail(decision.trail_len);
+        self.undone.push(Undone { decision, changes });
+        true
+    }
+
+    // Redoes the last undone decision, the grid is the same as before the
+    // undo. Returns false if there is nothing to redo, a new collapse clears
+    // what could be redone.
+    pub fn redo(&mut self) -> bool {
+        let Some(mut undone) = self.undone.pop() else {
+            return false;
+        };
+        let trail_len: usize = self.trail.len();
+        for (before, after) in &undone.changes {
+            self.trail.push(*before);
+            self.set_tile(after);
+        }
+        undone.decision.trail_len = trail_len;
+        self.decisions.push(undone.decision);
+        self.cells_changed(trail_len);
+        true
+    }
+
+    // Passes the cells changed since the trail had trail_len changes on.
+    fn cells_changed(&mut self, trail_len: usize) {
+        let changed: Vec<usize> = self.trail[trail_len..]
+            .iter()
+            .map(|change| change.grid_ind)
+            .collect();
+        self.notify(&changed);
+    }
+
+    fn notify(&mut self, changed: &[usize]) {
+        self.cell_selector
+            .update(&self.grid, &self.tileset, changed, &mut self.rng);
+        if let Some(marks) = &mut self.marks {
+            for grid_ind in changed {
+                marks.update(&self.grid, &self.tileset, *grid_ind, 0);
+            }
+        }
+    }
+
+    // The uncollapsed cell the cell selector wants to collapse next.
+    pub fn next_cell(&mut self) -> Result<usize, WfcError> {
+        self.cell_selector
+            .select(&self.grid, &self.tileset, &mut self.rng)
+    }
+
+    pub fn collapsed_count(&self) -> usize {
+        self.collapsed_count
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.collapsed_count == self.grid.tiles.len()
+    }
+
+    // Marks the uncollapsed tiles with the lowest entropy as can_be_collapsed
+    // and returns their indexes, for picking the next cell by hand. Only the
+    // cells marked last time and the cells changed since then are touched.
+    pub fn mark_lowest_entropy(&mut self) -> Result<Vec<usize>, WfcError> {
+        if self.marks.is_none() {
+            self.marks = Some(EntropyQueue::new(&self.grid, &self.tileset, || 0));
+            // any tile may be marked before the first call
+            self.marked = (0..self.grid.tiles.len()).collect();
+        }
+        for grid_ind in self.marked.drain(..) {
+            self.grid.tiles[grid_ind].can_be_collapsed = false;
+        }
+        let marks: &mut EntropyQueue = self.marks.as_mut().unwrap();
+        self.marked = marks.lowest_within(ENTROPY_EPSILON);
+        for grid_ind in &self.marked {
+            self.grid.tiles[*grid_ind].can_be_collapsed = true;
+        }
+        if self.marked.is_empty() {
+            return Err(WfcError::NoCandidateCells);
+        }
+        Ok(self.marked.clone())
+    }
+
+    // Collapses cells picked by the cell selector until the grid is done.
+    pub fn run(&mut self) -> Result<(), WfcError> {
+        self.run_with(|_| true).map(|_| ())
+    }
+
+    // Same as run, but calls on_step after every step and stops early when
+    // it returns false, for progress reports and cancelling. Returns whether
+    // the grid is done.
+    pub fn run_with(&mut self, mut on_step: impl FnMut(&Solver) -> bool) -> Result<bool, WfcError> {
+        while !self.is_done() {
+            let grid_ind: usize = self.next_cell()?;
+            self.collapse(grid_ind)?;
+            if !on_step(self) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // b allows a on its left, but a doesn't allow b on its right
+    const ASYMMETRIC: &str = r#"(
+        name: "asymmetric",
+        tiles: [
+            (name: "a", image: "a.png", left: ["a", "b"], right: ["a", "b"], top: ["a", "b"], btm: ["a", "b"]),
+            (name: "b", image: "b.png", left: ["a"], right: ["b"], top: ["b"], btm: ["a"]),
+        ],
+    )"#;
+
+    // any tile fits next to any other
+    const OPEN: &str = r#"(
+        name: "open",
+        tiles: [
+            (name: "a", image: "a.png", sockets: (left: "0", right: "0", top: "0", btm: "0")),
+            (name: "b", image: "b.png", sockets: (left: "0", right: "0", top: "0", btm: "0")),
+        ],
+    )"#;
+
+    // t0 must have t1 on its right while t1 needs t0 below it, which forces
+    // the solver to give up on some grids
+    const UNSOLVABLE: &str = r#"(
+        name: "unsolvable",
+        tiles: [
+            (name: "t0", image: "t0.png", left: ["t0"], right: ["t1"], top: ["t1"], btm: ["t1"]),
+            (name: "t1", image: "t1.png", left: ["t1"], right: ["t0", "t1"], top: ["t0", "t1"], btm: ["t0"]),
+        ],
+    )"#;
+
+    #[test]
+    fn collapse_rejects_collapsed_and_missing_cells() {
+        let tileset: Tileset = Tileset::from_ron(OPEN).unwrap();
+        let mut solver: Solver = Solver::new(Grid::new(3, 3, 0, &tileset), tileset, 10);
+        solver.collapse(0).unwrap();
+        assert_eq!(
+            solver.collapse(0),
+            Err(WfcError::AlreadyCollapsed { cell: (0, 0) })
+        );
+        assert_eq!(
+            solver.collapse(9),
+            Err(WfcError::CellOutOfRange { grid_ind: 9 })
+        );
+        let collapsed: usize = solver.grid.tiles.iter().filter(|t| t.collapsed).count();
+        assert_eq!(solver.collapsed_count(), collapsed);
+    }
+
+    #[test]
+    fn same_seed_and_collapses_give_the_same_grid() {
+        let tileset: Tileset = Tileset::from_ron(include_str!("../../assets/tiles.ron")).unwrap();
+        // collapses the cells in index order, skipping the ones propagation
+        // or a backtrack already took care of
+        let solve = |seed: u64| -> Vec<Option<TileOption>> {
+            let grid: Grid = Grid::new(10, 10, seed, &tileset);
+            let mut solver: Solver = Solver::new(grid, tileset.clone(), 1000);
+            while !solver.is_done() {
+                let grid_ind: usize = solver
+                    .grid
+                    .tiles
+                    .iter()
+                    .position(|tile| !tile.collapsed)
+                    .unwrap();
+                solver.collapse(grid_ind).unwrap();
+            }
+            solver
+                .grid
+                .tiles
+                .iter()
+                .map(|tile| tile.options.first())
+                .collect()
+        };
+        assert_eq!(solve(5), solve(5));
+        assert_ne!(solve(5), solve(6));
+    }
+
+    #[test]
+    fn undo_and_redo_restore_the_exact_grid() {
+        let tileset: Tileset = Tileset::from_ron(include_str!("../../assets/tiles.ron")).unwrap();
+        let mut solver: Solver = Solver::new(Grid::new(8, 8, 11, &tileset), tileset, 1000);
+        // the grid and the collapsed count before each decision and at the end
+        let snapshot = |solver: &Solver| format!("{:?} {}", solver.grid, solver.collapsed_count());
+        let mut snapshots: Vec<String> = vec![snapshot(&solver)];
+        for _ in 0..10 {
+            let grid_ind: usize = solver.next_cell().unwrap();
+            assert!(matches!(solver.collapse(grid_ind), Ok(Step::Collapsed(_))));
+            snapshots.push(snapshot(&solver));
+        }
+
+        for expected in snapshots.iter().rev().skip(1) {
+            assert!(solver.undo());
+            assert_eq!(&snapshot(&solver), expected);
+        }
+        assert!(!solver.undo());
+        for expected in snapshots.iter().skip(1) {
+            assert!(solver.redo());
+            assert_eq!(&snapshot(&solver), expected);
+        }
+        assert!(!solver.redo());
+
+        // a new collapse after an undo drops what could be redone
+        assert!(solver.undo());
+        let grid_ind: usize = solver.next_cell().unwrap();
+        solver.collapse(grid_ind).unwrap();
+        assert!(!solver.redo());
+    }
+
+    #[test]
+    fn solver_keeps_working_after_a_failed_run() {
+        let tileset: Tileset = Tileset::from_ron(UNSOLVABLE).unwrap();
+        let mut solver: Solver = Solver::new(Grid::new(4, 5, 849, &tileset), tileset, 1000);
+        assert!(solver.run().is_err());
+        // the failure undid every decision, so there is nothing left to undo
+        assert_eq!(solver.collapsed_count(), 0);
+        assert!(!solver.undo());
+        for _ in 0..20 {
+            let Ok(grid_ind) = solver.next_cell() else {
+                break;
+            };
+            let _ = solver.collapse(grid_ind);
+            let collapsed: usize = solver.grid.tiles.iter().filter(|t| t.collapsed).count();
+            assert_eq!(solver.collapsed_count(), collapsed);
+        }
+    }
+
+    #[test]
+    fn large_grids_are_solved() {
+        let tileset: Tileset = Tileset::from_ron(include_str!("../../assets/tiles.ron")).unwrap();
+        let solved: usize = (0..5)
+            .filter(|seed| {
+                let grid: Grid = Grid::new(100, 100, *seed, &tileset);
+                let mut solver: Solver = Solver::new(grid, tileset.clone(), 1000);
+                solver.run().is_ok() && crate::validate(&solver.grid, &tileset).is_empty()
+            })
+            .count();
+        assert_eq!(solved, 5);
+    }
+
+    #[test]
+    fn attempts_get_longer_until_one_hits_the_limit() {
+        let tileset: Tileset = Tileset::from_ron(include_str!("../../assets/tiles.ron")).unwrap();
+        // collapsing random cells runs into dead ends all the time
+        let mut solver: Solver = Solver::new(Grid::new(30, 30, 0, &tileset), tileset, 8)
+            .with_cell_selector(Box::new(crate::select::RandomCell::default()));
+        solver.restart_after = 1;
+        let mut restarts: usize = 0;
+        let err: WfcError = loop {
+            let grid_ind: usize = solver.next_cell().unwrap();
+            match solver.collapse(grid_ind) {
+                Ok(Step::Restarted) => restarts += 1,
+                Ok(_) => {}
+                Err(err) => break err,
+            }
+        };
+        // attempts of 1, 2 and 4 backtracks, then one which gives up at 8
+        assert_eq!(restarts, 3);
+        assert_eq!(solver.restarts, 3);
+        assert_eq!(err, WfcError::BacktrackLimit { backtracks: 15 });
+    }
+
+    #[test]
+    fn backtrack_limit_leaves_a_consistent_grid() {
+        let tileset: Tileset = Tileset::from_ron(ASYMMETRIC).unwrap();
+        for seed in 0..20 {
+            for max_backtracks in [0, 1, 3] {
+                let grid: Grid = Grid::new(6, 6, seed, &tileset);
+                let mut solver: Solver = Solver::new(grid, tileset.clone(), max_backtracks);
+                if solver.run().is_ok() {
+                    continue;
+                }
+                assert!(solver
+                    .grid
+                    .tiles
+                    .iter()
+                    .all(|tile| !tile.options.is_empty()));
+                let collapsed: usize = solver.grid.tiles.iter().filter(|t| t.collapsed).count();
+                assert_eq!(solver.collapsed_count(), collapsed);
+                assert!(crate::validate(&solver.grid, &tileset).is_empty());
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WfcError {
+    // Cell (i, j) lost its last option, it sits on the `direction` side of
+    // the cell whose options removed it.
+    Contradiction {
+        cell: (usize, usize),
+        direction: &'static str,
+    },
+    UnknownSide(String),
+    UnknownTile(String),
+    UnknownStrategy(String),
+    Tileset(String),
+    SaveFile(String),
+    Image(String),
+    EmptyOptions {
+        cell: (usize, usize),
+    },
+    AlreadyCollapsed {
+        cell: (usize, usize),
+    },
+    CellOutOfRange {
+        grid_ind: usize,
+    },
+    NoValidOption {
+        cell: (usize, usize),
+    },
+    NoCandidateCells,
+    BacktrackLimit {
+        backtracks: usize,
+    },
+}
+
+impl fmt::Display for WfcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WfcError::Contradiction { cell, direction } => write!(
+                f,
+                "contradiction: cell {:?} has no options left ({direction} of its neighbour)",
+                cell
+            ),
+            WfcError::UnknownSide(side) => write!(f, "unknown side: {side}"),
+            WfcError::UnknownTile(name) => write!(f, "unknown tile: {name}"),
+            WfcError::UnknownStrategy(name) => write!(f, "unknown strategy: {name}"),
+            WfcError::Tileset(msg) => write!(f, "bad tileset: {msg}"),
+            WfcError::SaveFile(msg) => write!(f, "bad save file: {msg}"),
+            WfcError::Image(msg) => write!(f, "image error: {msg}"),
+            WfcError::EmptyOptions { cell } => write!(f, "cell {:?} has zero options", cell),
+            WfcError::AlreadyCollapsed { cell } => {
+                write!(f, "cell {:?} is already collapsed", cell)
+            }
+            WfcError::CellOutOfRange { grid_ind } => {
+                write!(f, "cell index {grid_ind} is out of the grid")
+            }
+            WfcError::NoValidOption { cell } => {
+                write!(f, "can't find option to collapse cell {:?}", cell)
+            }
+            WfcError::NoCandidateCells => write!(f, "no cells left to collapse"),
+            WfcError::BacktrackLimit { backtracks } => {
+                write!(f, "gave up after {backtracks} backtracks")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WfcError {}
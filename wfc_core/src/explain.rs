@@ -0,0 +1,162 @@
+use std::fmt;
+
+use crate::grid::Grid;
+use crate::options::OptionSet;
+use crate::solver::{Cause, Change};
+use crate::tileset::Tileset;
+
+// Why a cell ran out of options. Each step says which options a cell lost
+// and which neighbour removed them, the next step explains that neighbour,
+// until the decision everything started from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    pub cell: (usize, usize),
+    pub steps: Vec<String>,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cell {:?} has no options left", self.cell)?;
+        for step in &self.steps {
+            write!(f, "\n  {step}")?;
+        }
+        Ok(())
+    }
+}
+
+// Follows the causes on the trail back from the last change of the cell at
+// failed_ind, the grid is the state right after the trail.
+pub(crate) fn explain(
+    grid: &Grid,
+    tileset: &Tileset,
+    trail: &[Change],
+    failed_ind: usize,
+) -> Explanation {
+    let coords =
+        |grid_ind: usize| -> (usize, usize) { (grid.tiles[grid_ind].i, grid.tiles[grid_ind].j) };
+    let names = |options: OptionSet| -> String {
+        let names: Vec<&str> = options
+            .iter()
+            .map(|tile_opt| tileset.tiles[tile_opt.index()].name.as_str())
+            .collect();
+        names.join("|")
+    };
+    // options of the cell right after the trail had trail_ind + 1 changes
+    let options_after = |grid_ind: usize, trail_ind: usize| -> OptionSet {
+        trail[trail_ind + 1..]
+            .iter()
+            .find(|change| change.grid_ind == grid_ind)
+            .map_or(grid.tiles[grid_ind].options, |change| change.options)
+    };
+    // decisions are numbered from 1 in the order they are on the trail
+    let decision_number = |trail_ind: usize| -> usize {
+        trail[..=trail_ind]
+            .iter()
+            .filter(|change| change.cause == Cause::Decision)
+            .count()
+    };
+    // the decision or ban whose propagation made the change at trail_ind
+    let made_by = |trail_ind: usize| -> String {
+        let root: Option<usize> = trail[..trail_ind]
+            .iter()
+            .rposition(|change| !matches!(change.cause, Cause::Neighbour(_)));
+        match root {
+            Some(root) if trail[root].cause == Cause::Decision => {
+                format!("decision {}", decision_number(root))
+            }
+            Some(_) => String::from("backtracking"),
+            None => String::from("start"),
+        }
+    };
+
+    let mut steps: Vec<String> = vec![];
+    let mut target: usize = failed_ind;
+    for (trail_ind, change) in trail.iter().enumerate().rev() {
+        if change.grid_ind != target {
+            continue;
+        }
+        let cell: (usize, usize) = coords(target);
+        let after: OptionSet = options_after(target, trail_ind);
+        let lost: OptionSet = change
+            .options
+            .iter()
+            .filter(|tile_opt| !after.contains(*tile_opt))
+            .collect();
+        match change.cause {
+            Cause::Neighbour(source) => {
+                steps.push(format!(
+                    "cell {cell:?} lost {} because {:?}={} ({})",
+                    names(lost),
+                    coords(source),
+                    names(options_after(source, trail_ind)),
+                    made_by(trail_ind)
+                ));
+                target = source;
+            }
+            Cause::Decision => {
+                steps.push(format!(
+                    "cell {cell:?} was collapsed to {} by decision {}",
+                    names(after),
+                    decision_number(trail_ind)
+                ));
+                break;
+            }
+            Cause::Ban(_) => {
+                steps.push(format!(
+                    "cell {cell:?} lost {} when backtracking, it led to a contradiction before",
+                    names(lost)
+                ));
+                break;
+            }
+        }
+    }
+    Explanation {
+        cell: coords(failed_ind),
+        steps,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::WfcError;
+    use crate::solver::propagate;
+
+    // a, b and c only fit in that order from left to right, round and round
+    const CYCLE: &str = r#"(
+        name: "cycle",
+        tiles: [
+            (name: "a", image: "a.png", left: ["c"], right: ["b"]),
+            (name: "b", image: "b.png", left: ["a"], right: ["c"]),
+            (name: "c", image: "c.png", left: ["b"], right: ["a"]),
+        ],
+    )"#;
+
+    #[test]
+    fn follows_the_chain_back_to_the_decision() {
+        let tileset: Tileset = Tileset::from_ron(CYCLE).unwrap();
+        let a: OptionSet = OptionSet::single(tileset.find_tile("a").unwrap());
+        let mut grid: Grid = Grid::new(3, 1, 0, &tileset);
+        grid.tiles[2].options = a;
+
+        // a on the left forces b in the middle, which leaves nothing for
+        // the right cell
+        let mut trail: Vec<Change> = vec![Change::of(&grid, 0, Cause::Decision)];
+        grid.tiles[0].options = a;
+        grid.tiles[0].collapsed = true;
+        let propagated: Result<(), WfcError> = propagate(&mut grid, &tileset, 0, &mut trail);
+        assert!(matches!(
+            propagated,
+            Err(WfcError::Contradiction { cell: (0, 2), .. })
+        ));
+
+        let explanation: Explanation = explain(&grid, &tileset, &trail, 2);
+        assert_eq!(
+            explanation.to_string(),
+            "cell (0, 2) has no options left\n  \
+             cell (0, 2) lost a because (0, 1)=b (decision 1)\n  \
+             cell (0, 1) lost a|c because (0, 0)=a (decision 1)\n  \
+             cell (0, 0) was collapsed to a by decision 1"
+        );
+    }
+}
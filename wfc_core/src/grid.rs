@@ -0,0 +1,52 @@
+use crate::error::WfcError;
+use crate::tile::Tile;
+use crate::tileset::Tileset;
+
+pub const SIDES: [&str; 4] = ["left", "right", "top", "btm"];
+
+// Tiles are stored row by row, the tile at row i and column j lives at
+// i * width + j.
+#[derive(Debug, Clone)]
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+    pub tileset: String,
+    pub tiles: Vec<Tile>,
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize, seed: u64, tileset: &Tileset) -> Grid {
+        let mut tiles: Vec<Tile> = vec![];
+        for i in 0..height {
+            for j in 0..width {
+                tiles.push(Tile::new(i, j, tileset.all_options()));
+            }
+        }
+        Grid {
+            width,
+            height,
+            seed,
+            tileset: tileset.name.clone(),
+            tiles,
+        }
+    }
+
+    pub fn index(&self, i: usize, j: usize) -> usize {
+        i * self.width + j
+    }
+
+    pub fn neighbour_ind(&self, i: usize, j: usize, side: &str) -> Result<Option<usize>, WfcError> {
+        let (poss_i, poss_j): (i32, i32) = match side {
+            "left" => (i as i32, j as i32 - 1),
+            "right" => (i as i32, j as i32 + 1),
+            "top" => (i as i32 - 1, j as i32),
+            "btm" => (i as i32 + 1, j as i32),
+            _ => return Err(WfcError::UnknownSide(side.to_string())),
+        };
+        if poss_i < 0 || poss_j < 0 || poss_i >= self.height as i32 || poss_j >= self.width as i32 {
+            return Ok(None);
+        }
+        Ok(Some(self.index(poss_i as usize, poss_j as usize)))
+    }
+}
@@ -0,0 +1,38 @@
+pub mod analysis;
+pub mod backtrack;
+pub mod error;
+pub mod explain;
+pub mod grid;
+pub mod options;
+pub mod queue;
+pub mod render;
+pub mod rules;
+pub mod save;
+pub mod select;
+pub mod solver;
+pub mod tile;
+pub mod tileset;
+pub mod validate;
+
+pub use analysis::{analyze_rules, RuleReport};
+pub use backtrack::{Solver, Step, RESTART_AFTER};
+pub use error::WfcError;
+pub use explain::Explanation;
+pub use grid::{Grid, SIDES};
+pub use options::{OptionSet, MAX_TILES};
+pub use queue::EntropyQueue;
+pub use render::{encode_png, export_png, render_ascii, render_grid, RenderOptions};
+pub use rules::{find_intesection, get_possible_options};
+pub use save::SAVE_VERSION;
+pub use select::{
+    cell_selector_by_name, value_selector_by_name, CellSelector, DeterministicFirst, Hilbert,
+    LeastConstraining, MinEntropy, MinRemainingValues, RandomCell, Scanline, Spiral, ValueSelector,
+    WeightedRandom, CELL_SELECTORS, VALUE_SELECTORS,
+};
+pub use solver::{
+    all_cell_collapsed, check_side, entropy, find_proper_tile_option, propagate,
+    shuffle_tile_options, Cause, Change,
+};
+pub use tile::{Tile, TileOption};
+pub use tileset::{Sockets, Symmetry, TileDef, Tileset};
+pub use validate::{validate, Violation};
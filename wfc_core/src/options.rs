@@ -0,0 +1,105 @@
+use std::ops::{BitAnd, BitOr};
+
+use crate::tile::TileOption;
+
+pub const MAX_TILES: usize = 256;
+const WORDS: usize = MAX_TILES / 64;
+
+// Fixed size bitset of tile options, bit n set means TileOption(n) is still
+// possible. It is Copy, so narrowing a cell never allocates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct OptionSet([u64; WORDS]);
+
+impl OptionSet {
+    pub fn empty() -> OptionSet {
+        OptionSet([0; WORDS])
+    }
+
+    // TileOption(0) to TileOption(count - 1)
+    pub fn full(count: usize) -> OptionSet {
+        let mut set: OptionSet = OptionSet::empty();
+        for ind in 0..count {
+            set.insert(TileOption(ind));
+        }
+        set
+    }
+
+    pub fn single(opt: TileOption) -> OptionSet {
+        let mut set: OptionSet = OptionSet::empty();
+        set.insert(opt);
+        set
+    }
+
+    pub fn insert(&mut self, opt: TileOption) {
+        self.0[opt.index() / 64] |= 1 << (opt.index() % 64);
+    }
+
+    pub fn remove(&mut self, opt: TileOption) {
+        self.0[opt.index() / 64] &= !(1 << (opt.index() % 64));
+    }
+
+    pub fn contains(&self, opt: TileOption) -> bool {
+        self.0[opt.index() / 64] & (1 << (opt.index() % 64)) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+
+    pub fn first(&self) -> Option<TileOption> {
+        self.iter().next()
+    }
+
+    // options in increasing index order
+    pub fn iter(&self) -> impl Iterator<Item = TileOption> + '_ {
+        self.0.iter().enumerate().flat_map(|(word_ind, word)| {
+            let mut bits: u64 = *word;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let bit: usize = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                Some(TileOption(word_ind * 64 + bit))
+            })
+        })
+    }
+}
+
+impl BitAnd for OptionSet {
+    type Output = OptionSet;
+
+    fn bitand(self, other: OptionSet) -> OptionSet {
+        let mut words: [u64; WORDS] = self.0;
+        for (word, other_word) in words.iter_mut().zip(other.0) {
+            *word &= other_word;
+        }
+        OptionSet(words)
+    }
+}
+
+impl BitOr for OptionSet {
+    type Output = OptionSet;
+
+    fn bitor(self, other: OptionSet) -> OptionSet {
+        let mut words: [u64; WORDS] = self.0;
+        for (word, other_word) in words.iter_mut().zip(other.0) {
+            *word |= other_word;
+        }
+        OptionSet(words)
+    }
+}
+
+impl FromIterator<TileOption> for OptionSet {
+    fn from_iter<I: IntoIterator<Item = TileOption>>(iter: I) -> OptionSet {
+        let mut set: OptionSet = OptionSet::empty();
+        for opt in iter {
+            set.insert(opt);
+        }
+        set
+    }
+}
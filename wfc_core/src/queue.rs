@@ -0,0 +1,124 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::grid::Grid;
+use crate::solver::entropy;
+use crate::tileset::Tileset;
+
+// Min-heap of the uncollapsed cells keyed by entropy. A cell is pushed again
+// whenever its options change and the older entries of that cell are
+// skipped once they reach the top, so keeping the queue up to date costs
+// O(log n) per changed cell and the grid is never scanned.
+#[derive(Debug, Clone, Default)]
+pub struct EntropyQueue {
+    heap: BinaryHeap<Entry>,
+    // bumped on every update, entries with an older version are outdated
+    versions: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    entropy: f32,
+    tie_break: u64,
+    grid_ind: usize,
+    version: u32,
+}
+
+// reversed, so the BinaryHeap pops the lowest entropy first, equal
+// entropies by the lowest tie break key
+impl Ord for Entry {
+    fn cmp(&self, other: &Entry) -> Ordering {
+        other
+            .entropy
+            .total_cmp(&self.entropy)
+            .then_with(|| other.tie_break.cmp(&self.tie_break))
+            .then_with(|| other.grid_ind.cmp(&self.grid_ind))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Entry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Entry) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl EntropyQueue {
+    // Cells with equal entropy come out in the order of their tie break
+    // keys, random keys give a random order.
+    pub fn new(grid: &Grid, tileset: &Tileset, mut tie_break: impl FnMut() -> u64) -> EntropyQueue {
+        let mut queue: EntropyQueue = EntropyQueue {
+            heap: BinaryHeap::with_capacity(grid.tiles.len()),
+            versions: vec![0; grid.tiles.len()],
+        };
+        for grid_ind in 0..grid.tiles.len() {
+            queue.update(grid, tileset, grid_ind, tie_break());
+        }
+        queue
+    }
+
+    // Re-keys the cell after its options changed, a collapsed cell is just
+    // dropped from the queue.
+    pub fn update(&mut self, grid: &Grid, tileset: &Tileset, grid_ind: usize, tie_break: u64) {
+        self.versions[grid_ind] = self.versions[grid_ind].wrapping_add(1);
+        let tile = &grid.tiles[grid_ind];
+        if !tile.collapsed {
+            self.heap.push(Entry {
+                entropy: entropy(tile, tileset),
+                tie_break,
+                grid_ind,
+                version: self.versions[grid_ind],
+            });
+        }
+        // outdated entries pile up during long solves
+        if self.heap.len() > 4 * self.versions.len() {
+            let versions: &Vec<u32> = &self.versions;
+            self.heap
+                .retain(|entry| entry.version == versions[entry.grid_ind]);
+        }
+    }
+
+    // Index of the uncollapsed cell with the lowest entropy.
+    pub fn lowest(&mut self) -> Option<usize> {
+        self.drop_outdated();
+        self.heap.peek().map(|entry| entry.grid_ind)
+    }
+
+    // Indexes of all uncollapsed cells less than epsilon above the lowest
+    // entropy, lowest first.
+    pub fn lowest_within(&mut self, epsilon: f32) -> Vec<usize> {
+        self.drop_outdated();
+        let Some(lowest) = self.heap.peek().map(|entry| entry.entropy) else {
+            return vec![];
+        };
+        let mut taken: Vec<Entry> = vec![];
+        while let Some(entry) = self.heap.peek().copied() {
+            if entry.entropy - lowest >= epsilon {
+                break;
+            }
+            self.heap.pop();
+            if entry.version == self.versions[entry.grid_ind] {
+                taken.push(entry);
+            }
+        }
+        let grid_inds: Vec<usize> = taken.iter().map(|entry| entry.grid_ind).collect();
+        self.heap.extend(taken);
+        grid_inds
+    }
+
+    fn drop_outdated(&mut self) {
+        while let Some(entry) = self.heap.peek() {
+            if entry.version == self.versions[entry.grid_ind] {
+                break;
+            }
+            self.heap.pop();
+        }
+    }
+}
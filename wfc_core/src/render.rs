@@ -0,0 +1,187 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use image::imageops::{self, FilterType};
+use image::{ImageFormat, Rgba, RgbaImage};
+
+use crate::error::WfcError;
+use crate::grid::Grid;
+use crate::tileset::Tileset;
+
+// Layout of a rendered grid, the defaults match the game window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    pub tile_size: u32,
+    pub gap: u32,
+    // gaps and uncollapsed cells
+    pub background: [u8; 4],
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            tile_size: 50,
+            gap: 10,
+            background: [0, 0, 0, 255],
+        }
+    }
+}
+
+// Draws the collapsed cells of the grid on the CPU, no GPU or window is
+// needed. Tile images are read from assets_dir, the same folder the game
+// loads them from, and scaled to the tile size.
+pub fn render_grid(
+    grid: &Grid,
+    tileset: &Tileset,
+    assets_dir: impl AsRef<Path>,
+    options: &RenderOptions,
+) -> Result<RgbaImage, WfcError> {
+    if options.tile_size == 0 || grid.width == 0 || grid.height == 0 {
+        return Err(WfcError::Image(String::from(
+            "tile size and grid size must be positive",
+        )));
+    }
+    let tile_images: Vec<RgbaImage> = tileset
+        .tiles
+        .iter()
+        .map(|tile| {
+            let path: PathBuf = assets_dir.as_ref().join(&tile.image);
+            let image: RgbaImage = image::open(&path)
+                .map_err(|err| WfcError::Image(format!("can't read {}: {err}", path.display())))?
+                .to_rgba8();
+            let mut image: RgbaImage = imageops::resize(
+                &image,
+                options.tile_size,
+                options.tile_size,
+                FilterType::Triangle,
+            );
+            // same order as the sprites in the game: flip, then turn
+            if tile.mirrored {
+                imageops::flip_horizontal_in_place(&mut image);
+            }
+            for _ in 0..tile.rotation {
+                image = imageops::rotate90(&image);
+            }
+            Ok(image)
+        })
+        .collect::<Result<_, WfcError>>()?;
+
+    let step: u32 = options.tile_size + options.gap;
+    let width: u32 = grid.width as u32 * step - options.gap;
+    let height: u32 = grid.height as u32 * step - options.gap;
+    let mut canvas: RgbaImage = RgbaImage::from_pixel(width, height, Rgba(options.background));
+    for tile in &grid.tiles {
+        if !tile.collapsed {
+            continue;
+        }
+        let Some(tile_opt) = tile.options.first() else {
+            continue;
+        };
+        imageops::replace(
+            &mut canvas,
+            &tile_images[tile_opt.index()],
+            (tile.j as u32 * step) as i64,
+            (tile.i as u32 * step) as i64,
+        );
+    }
+    Ok(canvas)
+}
+
+pub fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, WfcError> {
+    let mut png: Cursor<Vec<u8>> = Cursor::new(vec![]);
+    image
+        .write_to(&mut png, ImageFormat::Png)
+        .map_err(|err| WfcError::Image(err.to_string()))?;
+    Ok(png.into_inner())
+}
+
+pub fn export_png(
+    grid: &Grid,
+    tileset: &Tileset,
+    assets_dir: impl AsRef<Path>,
+    options: &RenderOptions,
+    path: impl AsRef<Path>,
+) -> Result<(), WfcError> {
+    let path: &Path = path.as_ref();
+    let png: Vec<u8> = encode_png(&render_grid(grid, tileset, assets_dir, options)?)?;
+    std::fs::write(path, png)
+        .map_err(|err| WfcError::Image(format!("can't write {}: {err}", path.display())))
+}
+
+// One character per cell, rows separated by newlines. Collapsed cells show
+// the ascii of their tile, or a letter by tile index if it has none, cells
+// which aren't collapsed show '.'.
+pub fn render_ascii(grid: &Grid, tileset: &Tileset) -> String {
+    const FALLBACK: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+    let mut text: String = String::with_capacity((grid.width + 1) * grid.height);
+    for tile in &grid.tiles {
+        let glyph: char = match tile.options.first() {
+            Some(tile_opt) if tile.collapsed => tileset.tiles[tile_opt.index()]
+                .ascii
+                .as_deref()
+                .and_then(|ascii| ascii.chars().next())
+                .unwrap_or(FALLBACK.get(tile_opt.index()).map_or('?', |c| *c as char)),
+            _ => '.',
+        };
+        text.push(glyph);
+        if tile.j + 1 == grid.width {
+            text.push('\n');
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::OptionSet;
+    use crate::tile::TileOption;
+
+    #[test]
+    fn render_places_turned_tiles_on_the_background() {
+        let assets_dir: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets");
+        let tileset: Tileset = Tileset::from_ron(include_str!("../../assets/tiles.ron")).unwrap();
+        let mut grid: Grid = Grid::new(3, 2, 0, &tileset);
+        // up in the top left corner, up turned once in the bottom right,
+        // the others stay uncollapsed
+        for (grid_ind, name) in [(0, "up"), (5, "up_90")] {
+            let tile_opt: TileOption = tileset.find_tile(name).unwrap();
+            grid.tiles[grid_ind].options = OptionSet::single(tile_opt);
+            grid.tiles[grid_ind].collapsed = true;
+        }
+        let options: RenderOptions = RenderOptions {
+            tile_size: 8,
+            gap: 3,
+            background: [1, 2, 3, 255],
+        };
+        let canvas: RgbaImage = render_grid(&grid, &tileset, &assets_dir, &options).unwrap();
+        assert_eq!(canvas.dimensions(), (3 * 11 - 3, 2 * 11 - 3));
+
+        let up: RgbaImage = imageops::resize(
+            &image::open(assets_dir.join("up.png")).unwrap().to_rgba8(),
+            8,
+            8,
+            FilterType::Triangle,
+        );
+        let turned: RgbaImage = imageops::rotate90(&up);
+        let background: Rgba<u8> = Rgba(options.background);
+        for y in 0..8 {
+            for x in 0..8 {
+                assert_eq!(canvas.get_pixel(x, y), up.get_pixel(x, y));
+                assert_eq!(canvas.get_pixel(22 + x, 11 + y), turned.get_pixel(x, y));
+                // the uncollapsed cell next to up
+                assert_eq!(*canvas.get_pixel(11 + x, y), background);
+            }
+            // the gaps right of and below up
+            assert_eq!(*canvas.get_pixel(8, y), background);
+            assert_eq!(*canvas.get_pixel(y, 10), background);
+        }
+
+        // the PNG holds the same picture
+        let path: PathBuf = std::env::temp_dir().join("wfc_render_test.png");
+        export_png(&grid, &tileset, &assets_dir, &options, &path).unwrap();
+        let exported: RgbaImage = image::open(&path).unwrap().to_rgba8();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(exported, canvas);
+    }
+}
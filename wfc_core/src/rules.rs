@@ -0,0 +1,16 @@
+use crate::error::WfcError;
+use crate::options::OptionSet;
+use crate::tile::TileOption;
+use crate::tileset::Tileset;
+
+pub fn find_intesection(a: OptionSet, b: OptionSet) -> OptionSet {
+    a & b
+}
+
+pub fn get_possible_options(
+    tileset: &Tileset,
+    tile_opt: &TileOption,
+    side: &str,
+) -> Result<OptionSet, WfcError> {
+    tileset.possible_options(tile_opt, side)
+}
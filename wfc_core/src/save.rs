@@ -0,0 +1,284 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::WfcError;
+use crate::grid::Grid;
+use crate::options::OptionSet;
+use crate::tile::Tile;
+use crate::tileset::{TileDef, Tileset};
+
+// Bumped whenever the layout of SavedGrid changes.
+pub const SAVE_VERSION: u32 = 1;
+
+// A grid as it's written to a save file. Options are names of tiles in the
+// tileset named by `tileset`, so a save still loads after tiles are added
+// or reordered. Tiles are stored row by row like in Grid.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedGrid {
+    version: u32,
+    width: usize,
+    height: usize,
+    seed: u64,
+    tileset: String,
+    tiles: Vec<SavedTile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedTile {
+    options: Vec<String>,
+    collapsed: bool,
+    can_be_collapsed: bool,
+}
+
+// A grid for other tools, rows of cells from the top, null for a cell which
+// isn't collapsed.
+#[derive(Debug, Clone, Serialize)]
+struct ExportedGrid<'a> {
+    width: usize,
+    height: usize,
+    seed: u64,
+    tileset: &'a str,
+    rows: Vec<Vec<Option<ExportedTile<'a>>>>,
+}
+
+// The image is turned by `rotation` quarter turns clockwise after flipping
+// it left to right if `mirrored`.
+#[derive(Debug, Clone, Serialize)]
+struct ExportedTile<'a> {
+    name: &'a str,
+    image: &'a str,
+    rotation: u8,
+    mirrored: bool,
+}
+
+impl Grid {
+    fn to_saved(&self, tileset: &Tileset) -> SavedGrid {
+        SavedGrid {
+            version: SAVE_VERSION,
+            width: self.width,
+            height: self.height,
+            seed: self.seed,
+            tileset: self.tileset.clone(),
+            tiles: self
+                .tiles
+                .iter()
+                .map(|tile| SavedTile {
+                    options: tile
+                        .options
+                        .iter()
+                        .map(|opt| tileset.tiles[opt.index()].name.clone())
+                        .collect(),
+                    collapsed: tile.collapsed,
+                    can_be_collapsed: tile.can_be_collapsed,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_ron(&self, tileset: &Tileset) -> Result<String, WfcError> {
+        let saved: SavedGrid = self.to_saved(tileset);
+        // one line per tile
+        let config: ron::ser::PrettyConfig = ron::ser::PrettyConfig::new().depth_limit(2);
+        ron::ser::to_string_pretty(&saved, config)
+            .map_err(|err| WfcError::SaveFile(err.to_string()))
+    }
+
+    // The finished map for other tools: size, seed and the tile of every
+    // cell by name, with the image and how it's turned. Not a save file.
+    pub fn to_json(&self, tileset: &Tileset) -> Result<String, WfcError> {
+        let exported: ExportedGrid = ExportedGrid {
+            width: self.width,
+            height: self.height,
+            seed: self.seed,
+            tileset: &self.tileset,
+            rows: self
+                .tiles
+                .chunks(self.width.max(1))
+                .map(|row| {
+                    row.iter()
+                        .map(|tile| {
+                            let tile_opt = tile.options.first().filter(|_| tile.collapsed)?;
+                            let tile_def: &TileDef = &tileset.tiles[tile_opt.index()];
+                            Some(ExportedTile {
+                                name: &tile_def.name,
+                                image: &tile_def.image,
+                                rotation: tile_def.rotation,
+                                mirrored: tile_def.mirrored,
+                            })
+                        })
+                        .collect()
+                })
+                .collect(),
+        };
+        serde_json::to_string(&exported).map_err(|err| WfcError::SaveFile(err.to_string()))
+    }
+
+    // The tileset must have the name and the tiles the grid was saved with,
+    // in any order.
+    pub fn from_ron(saved: &str, tileset: &Tileset) -> Result<Grid, WfcError> {
+        let saved: SavedGrid =
+            ron::from_str(saved).map_err(|err| WfcError::SaveFile(err.to_string()))?;
+        if saved.version != SAVE_VERSION {
+            return Err(WfcError::SaveFile(format!(
+                "version {} isn't supported, expected {SAVE_VERSION}",
+                saved.version
+            )));
+        }
+        if saved.tileset != tileset.name {
+            return Err(WfcError::SaveFile(format!(
+                "saved with tileset {}, not {}",
+                saved.tileset, tileset.name
+            )));
+        }
+        if saved.width == 0 || saved.height == 0 || saved.tiles.len() != saved.width * saved.height
+        {
+            return Err(WfcError::SaveFile(format!(
+                "{} tiles don't fill a {}x{} grid",
+                saved.tiles.len(),
+                saved.width,
+                saved.height
+            )));
+        }
+
+        let mut tiles: Vec<Tile> = vec![];
+        for (grid_ind, saved_tile) in saved.tiles.into_iter().enumerate() {
+            let (i, j): (usize, usize) = (grid_ind / saved.width, grid_ind % saved.width);
+            let mut options: OptionSet = OptionSet::empty();
+            for name in &saved_tile.options {
+                let Some(tile_opt) = tileset.find_tile(name) else {
+                    return Err(WfcError::SaveFile(format!(
+                        "cell {:?} has tile {name}, the tileset has no such tile",
+                        (i, j)
+                    )));
+                };
+                options.insert(tile_opt);
+            }
+            if saved_tile.collapsed && options.len() != 1 {
+                return Err(WfcError::SaveFile(format!(
+                    "cell {:?} is collapsed but has {} options",
+                    (i, j),
+                    options.len()
+                )));
+            }
+            let mut tile: Tile = Tile::new(i, j, options);
+            tile.collapsed = saved_tile.collapsed;
+            tile.can_be_collapsed = saved_tile.can_be_collapsed;
+            tiles.push(tile);
+        }
+        Ok(Grid {
+            width: saved.width,
+            height: saved.height,
+            seed: saved.seed,
+            tileset: saved.tileset,
+            tiles,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, tileset: &Tileset) -> Result<(), WfcError> {
+        let path: &Path = path.as_ref();
+        std::fs::write(path, self.to_ron(tileset)?)
+            .map_err(|err| WfcError::SaveFile(format!("can't write {}: {err}", path.display())))
+    }
+
+    pub fn load(path: impl AsRef<Path>, tileset: &Tileset) -> Result<Grid, WfcError> {
+        let path: &Path = path.as_ref();
+        let saved: String = std::fs::read_to_string(path)
+            .map_err(|err| WfcError::SaveFile(format!("can't read {}: {err}", path.display())))?;
+        Grid::from_ron(&saved, tileset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtrack::Solver;
+
+    const TILESET: &str = include_str!("../../assets/tiles.ron");
+
+    fn solved_grid(tileset: &Tileset) -> Grid {
+        let mut solver: Solver = Solver::new(Grid::new(6, 4, 7, tileset), tileset.clone(), 100);
+        solver.collapse(0).unwrap();
+        solver.collapse(9).unwrap();
+        solver.grid
+    }
+
+    fn names(grid: &Grid, tileset: &Tileset) -> Vec<Vec<String>> {
+        grid.tiles
+            .iter()
+            .map(|tile| {
+                let mut names: Vec<String> = tile
+                    .options
+                    .iter()
+                    .map(|opt| tileset.tiles[opt.index()].name.clone())
+                    .collect();
+                names.sort();
+                names
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip_gives_the_same_grid() {
+        let tileset: Tileset = Tileset::from_ron(TILESET).unwrap();
+        let grid: Grid = solved_grid(&tileset);
+        let loaded: Grid = Grid::from_ron(&grid.to_ron(&tileset).unwrap(), &tileset).unwrap();
+        assert_eq!(format!("{loaded:?}"), format!("{grid:?}"));
+    }
+
+    #[test]
+    fn loads_with_reordered_tiles() {
+        let tileset: Tileset = Tileset::from_ron(TILESET).unwrap();
+        let grid: Grid = solved_grid(&tileset);
+        let saved: String = grid.to_ron(&tileset).unwrap();
+
+        // loading only looks at the names of the tiles
+        let mut reordered: Tileset = tileset.clone();
+        reordered.tiles.reverse();
+        let loaded: Grid = Grid::from_ron(&saved, &reordered).unwrap();
+        assert_eq!(names(&loaded, &reordered), names(&grid, &tileset));
+    }
+
+    #[test]
+    fn json_has_tile_names_by_row() {
+        let tileset: Tileset = Tileset::from_ron(TILESET).unwrap();
+        let mut grid: Grid = Grid::new(2, 2, 3, &tileset);
+        let up_90 = tileset.find_tile("up_90").unwrap();
+        grid.tiles[1].options = OptionSet::single(up_90);
+        grid.tiles[1].collapsed = true;
+        let json: serde_json::Value =
+            serde_json::from_str(&grid.to_json(&tileset).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "width": 2,
+                "height": 2,
+                "seed": 3,
+                "tileset": "basic",
+                "rows": [
+                    [null, {"name": "up_90", "image": "up.png", "rotation": 1, "mirrored": false}],
+                    [null, null],
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let tileset: Tileset = Tileset::from_ron(TILESET).unwrap();
+        let saved: String = solved_grid(&tileset).to_ron(&tileset).unwrap();
+        let bad_files: [String; 4] = [
+            saved.replace("version: 1", "version: 2"),
+            saved.replace("tileset: \"basic\"", "tileset: \"other\""),
+            saved.replacen("\"blank\"", "\"missing\"", 1),
+            saved.replace("width: 6", "width: 5"),
+        ];
+        for bad in &bad_files {
+            assert_ne!(bad, &saved);
+            assert!(matches!(
+                Grid::from_ron(bad, &tileset),
+                Err(WfcError::SaveFile(_))
+            ));
+        }
+    }
+}
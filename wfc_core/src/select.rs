@@ -0,0 +1,599 @@
+use std::fmt;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::error::WfcError;
+use crate::grid::{Grid, SIDES};
+use crate::options::OptionSet;
+use crate::queue::EntropyQueue;
+use crate::rules::{find_intesection, get_possible_options};
+use crate::solver::{shuffle_tile_options, ENTROPY_EPSILON};
+use crate::tile::{Tile, TileOption};
+use crate::tileset::Tileset;
+
+pub const CELL_SELECTORS: [&str; 6] = [
+    "min-entropy",
+    "mrv",
+    "scanline",
+    "spiral",
+    "hilbert",
+    "random",
+];
+
+pub const VALUE_SELECTORS: [&str; 3] = ["weighted", "least-constraining", "first"];
+
+// Strategy for choosing which uncollapsed cell the solver collapses next.
+pub trait CellSelector: fmt::Debug + Send + Sync {
+    fn select(
+        &mut self,
+        grid: &Grid,
+        tileset: &Tileset,
+        rng: &mut StdRng,
+    ) -> Result<usize, WfcError>;
+
+    // Called by the solver with the cells whose options changed, for
+    // selectors which keep their own index of the grid.
+    fn update(&mut self, _grid: &Grid, _tileset: &Tileset, _changed: &[usize], _rng: &mut StdRng) {}
+}
+
+pub fn cell_selector_by_name(name: &str) -> Result<Box<dyn CellSelector>, WfcError> {
+    match name {
+        "min-entropy" => Ok(Box::new(MinEntropy::default())),
+        "mrv" => Ok(Box::new(MinRemainingValues::default())),
+        "scanline" => Ok(Box::new(Scanline::default())),
+        "spiral" => Ok(Box::new(Spiral::default())),
+        "hilbert" => Ok(Box::new(Hilbert::default())),
+        "random" => Ok(Box::new(RandomCell::default())),
+        _ => Err(WfcError::UnknownStrategy(name.to_string())),
+    }
+}
+
+// Lowest weighted Shannon entropy, ties are broken randomly. The entropies
+// are kept in a queue which is built on the first select and then only
+// updated for changed cells. Every tie gets the same chance on every
+// select, a random key kept per cell would pass over the same cells again
+// and again and leave holes which often can't be filled.
+#[derive(Debug, Clone, Default)]
+pub struct MinEntropy {
+    queue: Option<EntropyQueue>,
+}
+
+impl CellSelector for MinEntropy {
+    fn select(
+        &mut self,
+        grid: &Grid,
+        tileset: &Tileset,
+        rng: &mut StdRng,
+    ) -> Result<usize, WfcError> {
+        let queue: &mut EntropyQueue = self
+            .queue
+            .get_or_insert_with(|| EntropyQueue::new(grid, tileset, || 0));
+        queue
+            .lowest_within(ENTROPY_EPSILON)
+            .choose(rng)
+            .copied()
+            .ok_or(WfcError::NoCandidateCells)
+    }
+
+    fn update(&mut self, grid: &Grid, tileset: &Tileset, changed: &[usize], _rng: &mut StdRng) {
+        let Some(queue) = &mut self.queue else {
+            return;
+        };
+        for grid_ind in changed {
+            queue.update(grid, tileset, *grid_ind, 0);
+        }
+    }
+}
+
+// Fewest options left, ties are broken randomly. The cells are kept in
+// buckets by their number of options, built on the first select and then
+// only updated for changed cells.
+#[derive(Debug, Clone, Default)]
+pub struct MinRemainingValues {
+    buckets: Option<CellBuckets>,
+}
+
+impl CellSelector for MinRemainingValues {
+    fn select(
+        &mut self,
+        grid: &Grid,
+        _tileset: &Tileset,
+        rng: &mut StdRng,
+    ) -> Result<usize, WfcError> {
+        let buckets: &mut CellBuckets = self
+            .buckets
+            .get_or_insert_with(|| CellBuckets::new(grid, options_left));
+        buckets
+            .lowest()
+            .and_then(|cells| cells.choose(rng))
+            .copied()
+            .ok_or(WfcError::NoCandidateCells)
+    }
+
+    fn update(&mut self, grid: &Grid, _tileset: &Tileset, changed: &[usize], _rng: &mut StdRng) {
+        if let Some(buckets) = &mut self.buckets {
+            buckets.update(grid, changed, options_left);
+        }
+    }
+}
+
+fn options_left(tile: &Tile) -> usize {
+    tile.options.len()
+}
+
+// Row by row, left to right.
+#[derive(Debug, Clone, Default)]
+pub struct Scanline {
+    order: CellOrder,
+}
+
+impl CellSelector for Scanline {
+    fn select(
+        &mut self,
+        grid: &Grid,
+        _tileset: &Tileset,
+        _rng: &mut StdRng,
+    ) -> Result<usize, WfcError> {
+        self.order.first_uncollapsed(grid, scanline_order)
+    }
+
+    fn update(&mut self, grid: &Grid, _tileset: &Tileset, changed: &[usize], _rng: &mut StdRng) {
+        self.order.update(grid, changed);
+    }
+}
+
+// Outwards from the centre of the grid in a square spiral.
+#[derive(Debug, Clone, Default)]
+pub struct Spiral {
+    order: CellOrder,
+}
+
+impl CellSelector for Spiral {
+    fn select(
+        &mut self,
+        grid: &Grid,
+        _tileset: &Tileset,
+        _rng: &mut StdRng,
+    ) -> Result<usize, WfcError> {
+        self.order.first_uncollapsed(grid, spiral_order)
+    }
+
+    fn update(&mut self, grid: &Grid, _tileset: &Tileset, changed: &[usize], _rng: &mut StdRng) {
+        self.order.update(grid, changed);
+    }
+}
+
+// Along a Hilbert curve, which keeps consecutive cells close together.
+#[derive(Debug, Clone, Default)]
+pub struct Hilbert {
+    order: CellOrder,
+}
+
+impl CellSelector for Hilbert {
+    fn select(
+        &mut self,
+        grid: &Grid,
+        _tileset: &Tileset,
+        _rng: &mut StdRng,
+    ) -> Result<usize, WfcError> {
+        self.order.first_uncollapsed(grid, hilbert_order)
+    }
+
+    fn update(&mut self, grid: &Grid, _tileset: &Tileset, changed: &[usize], _rng: &mut StdRng) {
+        self.order.update(grid, changed);
+    }
+}
+
+// Any uncollapsed cell. They are kept in one bucket, built on the first
+// select and then only updated for changed cells.
+#[derive(Debug, Clone, Default)]
+pub struct RandomCell {
+    buckets: Option<CellBuckets>,
+}
+
+impl CellSelector for RandomCell {
+    fn select(
+        &mut self,
+        grid: &Grid,
+        _tileset: &Tileset,
+        rng: &mut StdRng,
+    ) -> Result<usize, WfcError> {
+        let buckets: &mut CellBuckets = self
+            .buckets
+            .get_or_insert_with(|| CellBuckets::new(grid, |_| 0));
+        buckets
+            .lowest()
+            .and_then(|cells| cells.choose(rng))
+            .copied()
+            .ok_or(WfcError::NoCandidateCells)
+    }
+
+    fn update(&mut self, grid: &Grid, _tileset: &Tileset, changed: &[usize], _rng: &mut StdRng) {
+        if let Some(buckets) = &mut self.buckets {
+            buckets.update(grid, changed, |_| 0);
+        }
+    }
+}
+
+// Strategy for choosing the option a cell collapses to. The solver takes
+// the first option of the returned order which passes its look-ahead.
+pub trait ValueSelector: fmt::Debug + Send + Sync {
+    fn order(
+        &mut self,
+        grid: &Grid,
+        tileset: &Tileset,
+        grid_ind: usize,
+        rng: &mut StdRng,
+    ) -> Result<Vec<TileOption>, WfcError>;
+}
+
+pub fn value_selector_by_name(name: &str) -> Result<Box<dyn ValueSelector>, WfcError> {
+    match name {
+        "weighted" => Ok(Box::new(WeightedRandom)),
+        "least-constraining" => Ok(Box::new(LeastConstraining)),
+        "first" => Ok(Box::new(DeterministicFirst)),
+        _ => Err(WfcError::UnknownStrategy(name.to_string())),
+    }
+}
+
+// Random order, heavier tiles tend to come first.
+#[derive(Debug, Clone, Default)]
+pub struct WeightedRandom;
+
+impl ValueSelector for WeightedRandom {
+    fn order(
+        &mut self,
+        grid: &Grid,
+        tileset: &Tileset,
+        grid_ind: usize,
+        rng: &mut StdRng,
+    ) -> Result<Vec<TileOption>, WfcError> {
+        shuffle_tile_options(&grid.tiles[grid_ind], tileset, rng)
+    }
+}
+
+// Options which remove the fewest options from the neighbours come first,
+// ties keep the weighted random order.
+#[derive(Debug, Clone, Default)]
+pub struct LeastConstraining;
+
+impl ValueSelector for LeastConstraining {
+    fn order(
+        &mut self,
+        grid: &Grid,
+        tileset: &Tileset,
+        grid_ind: usize,
+        rng: &mut StdRng,
+    ) -> Result<Vec<TileOption>, WfcError> {
+        let options: Vec<TileOption> = WeightedRandom.order(grid, tileset, grid_ind, rng)?;
+        let tile: &Tile = &grid.tiles[grid_ind];
+        let mut removed_counts: Vec<(usize, TileOption)> = vec![];
+        for opt in options {
+            let mut removed: usize = 0;
+            for side in SIDES {
+                let Some(side_ind) = grid.neighbour_ind(tile.i, tile.j, side)? else {
+                    continue;
+                };
+                let side_tile: &Tile = &grid.tiles[side_ind];
+                if side_tile.collapsed {
+                    continue;
+                }
+                let kept: OptionSet = find_intesection(
+                    side_tile.options,
+                    get_possible_options(tileset, &opt, side)?,
+                );
+                removed += side_tile.options.len() - kept.len();
+            }
+            removed_counts.push((removed, opt));
+        }
+        removed_counts.sort_by_key(|(removed, _)| *removed);
+        Ok(removed_counts.into_iter().map(|(_, opt)| opt).collect())
+    }
+}
+
+// Options in tileset order, no randomness at all.
+#[derive(Debug, Clone, Default)]
+pub struct DeterministicFirst;
+
+impl ValueSelector for DeterministicFirst {
+    fn order(
+        &mut self,
+        grid: &Grid,
+        _tileset: &Tileset,
+        grid_ind: usize,
+        _rng: &mut StdRng,
+    ) -> Result<Vec<TileOption>, WfcError> {
+        Ok(grid.tiles[grid_ind].options.iter().collect())
+    }
+}
+
+// Visiting order of the cells, computed once per grid size. Every cell
+// before the cursor in the order is collapsed, so select only walks forward
+// from it and a cell which is uncollapsed again, by backtracking or undo,
+// moves the cursor back to it.
+#[derive(Debug, Clone, Default)]
+struct CellOrder {
+    width: usize,
+    height: usize,
+    order: Vec<usize>,
+    // position[grid_ind] is the index of the cell in order
+    position: Vec<usize>,
+    cursor: usize,
+}
+
+impl CellOrder {
+    fn first_uncollapsed(
+        &mut self,
+        grid: &Grid,
+        make_order: fn(usize, usize) -> Vec<usize>,
+    ) -> Result<usize, WfcError> {
+        if self.order.is_empty() || self.width != grid.width || self.height != grid.height {
+            self.width = grid.width;
+            self.height = grid.height;
+            self.order = make_order(grid.width, grid.height);
+            self.position = vec![0; self.order.len()];
+            for (order_ind, grid_ind) in self.order.iter().enumerate() {
+                self.position[*grid_ind] = order_ind;
+            }
+            self.cursor = 0;
+        }
+        while self
+            .order
+            .get(self.cursor)
+            .is_some_and(|ind| grid.tiles[*ind].collapsed)
+        {
+            self.cursor += 1;
+        }
+        self.order
+            .get(self.cursor)
+            .copied()
+            .ok_or(WfcError::NoCandidateCells)
+    }
+
+    fn update(&mut self, grid: &Grid, changed: &[usize]) {
+        for grid_ind in changed {
+            if let Some(order_ind) = self.position.get(*grid_ind) {
+                if !grid.tiles[*grid_ind].collapsed {
+                    self.cursor = self.cursor.min(*order_ind);
+                }
+            }
+        }
+    }
+}
+
+// Uncollapsed cells grouped by a key, bucket k holds the cells with key k
+// in no particular order. Moving a cell between buckets is O(1).
+#[derive(Debug, Clone, Default)]
+struct CellBuckets {
+    buckets: Vec<Vec<usize>>,
+    // (bucket, index in the bucket) of every cell, None once collapsed
+    slots: Vec<Option<(usize, usize)>>,
+}
+
+impl CellBuckets {
+    fn new(grid: &Grid, key: fn(&Tile) -> usize) -> CellBuckets {
+        let mut buckets: CellBuckets = CellBuckets {
+            buckets: vec![],
+            slots: vec![None; grid.tiles.len()],
+        };
+        let all: Vec<usize> = (0..grid.tiles.len()).collect();
+        buckets.update(grid, &all, key);
+        buckets
+    }
+
+    fn update(&mut self, grid: &Grid, changed: &[usize], key: fn(&Tile) -> usize) {
+        for grid_ind in changed {
+            self.remove(*grid_ind);
+            let tile: &Tile = &grid.tiles[*grid_ind];
+            if tile.collapsed {
+                continue;
+            }
+            let bucket: usize = key(tile);
+            if self.buckets.len() <= bucket {
+                self.buckets.resize(bucket + 1, vec![]);
+            }
+            self.slots[*grid_ind] = Some((bucket, self.buckets[bucket].len()));
+            self.buckets[bucket].push(*grid_ind);
+        }
+    }
+
+    fn remove(&mut self, grid_ind: usize) {
+        let Some((bucket, slot)) = self.slots[grid_ind].take() else {
+            return;
+        };
+        self.buckets[bucket].swap_remove(slot);
+        if let Some(moved) = self.buckets[bucket].get(slot) {
+            self.slots[*moved] = Some((bucket, slot));
+        }
+    }
+
+    // The cells of the lowest non-empty bucket.
+    fn lowest(&self) -> Option<&[usize]> {
+        self.buckets
+            .iter()
+            .find(|cells| !cells.is_empty())
+            .map(Vec::as_slice)
+    }
+}
+
+fn scanline_order(width: usize, height: usize) -> Vec<usize> {
+    (0..width * height).collect()
+}
+
+// Walks right 1, down 1, left 2, up 2, right 3, ... from the centre and
+// keeps the cells which are inside the grid.
+fn spiral_order(width: usize, height: usize) -> Vec<usize> {
+    let cells: usize = width * height;
+    let mut order: Vec<usize> = Vec::with_capacity(cells);
+    let (mut i, mut j): (i64, i64) = ((height as i64 - 1) / 2, (width as i64 - 1) / 2);
+    let steps: [(i64, i64); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+    let mut run_len: i64 = 1;
+    let mut step_ind: usize = 0;
+    order.push(i as usize * width + j as usize);
+    while order.len() < cells {
+        for _ in 0..2 {
+            let (di, dj): (i64, i64) = steps[step_ind % 4];
+            for _ in 0..run_len {
+                i += di;
+                j += dj;
+                if i >= 0 && j >= 0 && i < height as i64 && j < width as i64 {
+                    order.push(i as usize * width + j as usize);
+                }
+            }
+            step_ind += 1;
+        }
+        run_len += 1;
+    }
+    order
+}
+
+// Sorts cells by their distance along a Hilbert curve filling the smallest
+// power of two square which covers the grid.
+fn hilbert_order(width: usize, height: usize) -> Vec<usize> {
+    let side: usize = width.max(height).next_power_of_two();
+    let mut order: Vec<usize> = (0..width * height).collect();
+    order.sort_by_key(|ind| hilbert_distance(side, ind % width, ind / width));
+    order
+}
+
+fn hilbert_distance(side: usize, mut x: usize, mut y: usize) -> usize {
+    let mut distance: usize = 0;
+    let mut s: usize = side / 2;
+    while s > 0 {
+        let rx: usize = usize::from(x & s > 0);
+        let ry: usize = usize::from(y & s > 0);
+        distance += s * s * ((3 * rx) ^ ry);
+        // rotate the quadrant so the curve stays continuous
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    distance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtrack::Solver;
+
+    const OPEN: &str = r#"(
+        name: "open",
+        tiles: [
+            (name: "a", image: "a.png", sockets: (left: "0", right: "0", top: "0", btm: "0")),
+            (name: "b", image: "b.png", sockets: (left: "0", right: "0", top: "0", btm: "0")),
+        ],
+    )"#;
+
+    // a fits next to anything, b next to a and b, c only next to a
+    const NARROWING: &str = r#"(
+        name: "narrowing",
+        tiles: [
+            (name: "a", image: "a.png", left: ["a", "b", "c"], right: ["a", "b", "c"], top: ["a", "b", "c"], btm: ["a", "b", "c"]),
+            (name: "b", image: "b.png", left: ["a", "b"], right: ["a", "b"], top: ["a", "b"], btm: ["a", "b"]),
+            (name: "c", image: "c.png", left: ["a"], right: ["a"], top: ["a"], btm: ["a"]),
+        ],
+    )"#;
+
+    #[test]
+    fn orders_visit_every_cell_once() {
+        for (width, height) in [(1, 1), (1, 7), (6, 1), (4, 4), (5, 3), (3, 8), (17, 10)] {
+            for make_order in [scanline_order, spiral_order, hilbert_order] {
+                let mut order: Vec<usize> = make_order(width, height);
+                order.sort();
+                assert_eq!(order, (0..width * height).collect::<Vec<usize>>());
+            }
+        }
+        // the spiral starts in the middle
+        assert_eq!(spiral_order(5, 3)[0], 7);
+    }
+
+    #[test]
+    fn min_entropy_breaks_ties_randomly() {
+        let tileset: Tileset = Tileset::from_ron(OPEN).unwrap();
+        let grid: Grid = Grid::new(50, 50, 0, &tileset);
+        let mut picks: Vec<usize> = (0..200)
+            .map(|seed| {
+                let mut rng: StdRng = StdRng::seed_from_u64(seed);
+                MinEntropy::default()
+                    .select(&grid, &tileset, &mut rng)
+                    .unwrap()
+            })
+            .collect();
+        picks.sort();
+        // all cells tie, so the picks are spread over the whole grid
+        assert!(
+            picks[100] > 500 && picks[100] < 2000,
+            "median {}",
+            picks[100]
+        );
+        picks.dedup();
+        assert!(picks.len() > 150);
+    }
+
+    #[test]
+    fn least_constraining_puts_the_loosest_option_first() {
+        let tileset: Tileset = Tileset::from_ron(NARROWING).unwrap();
+        let grid: Grid = Grid::new(3, 3, 0, &tileset);
+        let names = |order: Vec<TileOption>| -> Vec<String> {
+            order
+                .iter()
+                .map(|opt| tileset.tiles[opt.index()].name.clone())
+                .collect()
+        };
+        // in the middle a removes nothing, b removes c from the four
+        // neighbours and c removes b and c
+        for seed in 0..20 {
+            let mut rng: StdRng = StdRng::seed_from_u64(seed);
+            let order: Vec<TileOption> = LeastConstraining
+                .order(&grid, &tileset, 4, &mut rng)
+                .unwrap();
+            assert_eq!(names(order), vec!["a", "b", "c"]);
+        }
+    }
+
+    #[test]
+    fn deterministic_first_keeps_the_tileset_order() {
+        let tileset: Tileset = Tileset::from_ron(NARROWING).unwrap();
+        let mut grid: Grid = Grid::new(3, 3, 0, &tileset);
+        grid.tiles[4]
+            .options
+            .remove(tileset.find_tile("b").unwrap());
+        for seed in 0..5 {
+            let mut rng: StdRng = StdRng::seed_from_u64(seed);
+            let all: Vec<TileOption> = DeterministicFirst
+                .order(&grid, &tileset, 0, &mut rng)
+                .unwrap();
+            assert_eq!(all, vec![TileOption(0), TileOption(1), TileOption(2)]);
+            let left: Vec<TileOption> = DeterministicFirst
+                .order(&grid, &tileset, 4, &mut rng)
+                .unwrap();
+            assert_eq!(left, vec![TileOption(0), TileOption(2)]);
+        }
+    }
+
+    #[test]
+    fn selectors_go_back_to_undone_cells() {
+        let tileset: Tileset = Tileset::from_ron(OPEN).unwrap();
+        for name in CELL_SELECTORS {
+            let mut solver: Solver = Solver::new(Grid::new(6, 5, 1, &tileset), tileset.clone(), 0)
+                .with_cell_selector(cell_selector_by_name(name).unwrap());
+            let mut picks: Vec<usize> = vec![];
+            for _ in 0..10 {
+                picks.push(solver.next_cell().unwrap());
+                solver.collapse(picks[picks.len() - 1]).unwrap();
+            }
+            assert!(solver.undo() && solver.undo());
+            if ["scanline", "spiral", "hilbert"].contains(&name) {
+                assert_eq!(solver.next_cell().unwrap(), picks[8], "{name}");
+            }
+            solver.run().unwrap();
+            assert!(solver.is_done(), "{name}");
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use rand::prelude::*;
+
+use crate::error::WfcError;
+use crate::grid::{Grid, SIDES};
+use crate::options::OptionSet;
+use crate::rules::{find_intesection, get_possible_options};
+use crate::tile::{Tile, TileOption};
+use crate::tileset::Tileset;
+
+pub(crate) const ENTROPY_EPSILON: f32 = 1e-4;
+
+// Why the options of a tile changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cause {
+    // the tile was collapsed by a decision
+    Decision,
+    // backtracking banned the option a decision chose for the tile, the
+    // index of the ban in the solver's list of ban reasons
+    Ban(usize),
+    // propagation from the neighbour at this grid index
+    Neighbour(usize),
+}
+
+// State of a tile right before it was changed and the cause of the change.
+// Restoring the changes of a trail in reverse order undoes them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Change {
+    pub grid_ind: usize,
+    pub options: OptionSet,
+    pub collapsed: bool,
+    pub cause: Cause,
+}
+
+impl Change {
+    pub fn of(grid: &Grid, grid_ind: usize, cause: Cause) -> Change {
+        Change {
+            grid_ind,
+            options: grid.tiles[grid_ind].options,
+            collapsed: grid.tiles[grid_ind].collapsed,
+            cause,
+        }
+    }
+
+    pub fn restore(&self, grid: &mut Grid) {
+        let tile: &mut Tile = &mut grid.tiles[self.grid_ind];
+        tile.options = self.options;
+        tile.collapsed = self.collapsed;
+    }
+}
+
+// AC-3 style propagation: starting from the changed cell, keep removing
+// options that have no support in a neighbour until nothing changes. Every
+// narrowed cell is recorded on the trail before it is changed, together
+// with the neighbour which narrowed it.
+pub fn propagate(
+    grid: &mut Grid,
+    tileset: &Tileset,
+    changed_ind: usize,
+    trail: &mut Vec<Change>,
+) -> Result<(), WfcError> {
+    Propagator::default().propagate(grid, tileset, changed_ind, trail)
+}
+
+// Runs propagate with a flag per cell for the cells on the worklist, so
+// none is added twice. The solver keeps one to not allocate the flags on
+// every call, they are all false again after each propagation.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Propagator {
+    queued: Vec<bool>,
+}
+
+impl Propagator {
+    pub(crate) fn propagate(
+        &mut self,
+        grid: &mut Grid,
+        tileset: &Tileset,
+        changed_ind: usize,
+        trail: &mut Vec<Change>,
+    ) -> Result<(), WfcError> {
+        let queued: &mut Vec<bool> = &mut self.queued;
+        let mut worklist: Vec<usize> = vec![changed_ind];
+        queued.resize(grid.tiles.len(), false);
+        queued[changed_ind] = true;
+        while let Some(cell_ind) = worklist.pop() {
+            queued[cell_ind] = false;
+            let (i, j) = (grid.tiles[cell_ind].i, grid.tiles[cell_ind].j);
+            for (dir_ind, side) in SIDES.iter().enumerate() {
+                let Some(side_ind) = grid.neighbour_ind(i, j, side)? else {
+                    continue;
+                };
+
+                let allowed: OptionSet = grid.tiles[cell_ind]
+                    .options
+                    .iter()
+                    .fold(OptionSet::empty(), |allowed, cell_opt| {
+                        allowed | tileset.compatible(&cell_opt, dir_ind)
+                    });
+
+                let new_options: OptionSet =
+                    find_intesection(grid.tiles[side_ind].options, allowed);
+                if new_options == grid.tiles[side_ind].options {
+                    continue;
+                }
+                trail.push(Change::of(grid, side_ind, Cause::Neighbour(cell_ind)));
+                let side_cell: &mut Tile = &mut grid.tiles[side_ind];
+                side_cell.options = new_options;
+                if side_cell.options.is_empty() {
+                    for queued_ind in worklist {
+                        queued[queued_ind] = false;
+                    }
+                    return Err(WfcError::Contradiction {
+                        cell: (side_cell.i, side_cell.j),
+                        direction: side,
+                    });
+                }
+                if !queued[side_ind] {
+                    queued[side_ind] = true;
+                    worklist.push(side_ind);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn all_cell_collapsed(grid: &Grid) -> bool {
+    grid.tiles.iter().all(|cell| cell.collapsed)
+}
+
+// Weighted Shannon entropy of the options left in the tile.
+pub fn entropy(tile: &Tile, tileset: &Tileset) -> f32 {
+    let mut sum_of_weights: f32 = 0.0;
+    let mut sum_of_weight_log_weights: f32 = 0.0;
+    for opt in tile.options.iter() {
+        let weight: f32 = tileset.tiles[opt.index()].weight;
+        sum_of_weights += weight;
+        sum_of_weight_log_weights += weight * weight.ln();
+    }
+    sum_of_weights.ln() - sum_of_weight_log_weights / sum_of_weights
+}
+
+pub fn check_side(
+    grid: &Grid,
+    tileset: &Tileset,
+    tile_to_collapse_i: usize,
+    tile_to_collapse_j: usize,
+    tile_to_collapse_opt: &TileOption,
+    side: &str,
+) -> Result<bool, WfcError> {
+    let mut current_tile_opt: TileOption = *tile_to_collapse_opt;
+    let mut side_ind: Option<usize> =
+        grid.neighbour_ind(tile_to_collapse_i, tile_to_collapse_j, side)?;
+    while let Some(ind) = side_ind {
+        let side_tile: &Tile = &grid.tiles[ind];
+        let side_possible_opts: OptionSet = get_possible_options(tileset, &current_tile_opt, side)?;
+        let intersect: OptionSet = find_intesection(side_tile.options, side_possible_opts);
+        if intersect.is_empty() {
+            return Ok(false);
+        } else if side_tile.collapsed {
+            // propagation already made the cells past a collapsed one agree
+            // with it, walking on only costs a pass over the row or column
+            break;
+        } else if intersect.len() == 1 {
+            current_tile_opt = intersect.first().unwrap();
+        } else {
+            break;
+        }
+        side_ind = grid.neighbour_ind(side_tile.i, side_tile.j, side)?;
+    }
+    Ok(true)
+}
+
+// First of the given options, in order, which passes the look-ahead on
+// every side of the tile.
+pub fn find_proper_tile_option(
+    grid: &Grid,
+    tileset: &Tileset,
+    tile_to_collapse: &Tile,
+    options: &[TileOption],
+) -> Result<TileOption, WfcError> {
+    // check opt for possible collapse
+    'options: for tile_to_collapse_opt in options {
+        for side in SIDES {
+            let side_is_ok: bool = check_side(
+                grid,
+                tileset,
+                tile_to_collapse.i,
+                tile_to_collapse.j,
+                tile_to_collapse_opt,
+                side,
+            )?;
+            if !side_is_ok {
+                continue 'options;
+            }
+        }
+        return Ok(*tile_to_collapse_opt);
+    }
+    Err(WfcError::NoValidOption {
+        cell: (tile_to_collapse.i, tile_to_collapse.j),
+    })
+}
+
+// Weighted shuffle of the options of the tile, heavier options tend to come
+// first. Each option gets the key u^(1/weight) for a uniform random u and
+// options are sorted by it.
+pub fn shuffle_tile_options<R: Rng>(
+    tile: &Tile,
+    tileset: &Tileset,
+    rng: &mut R,
+) -> Result<Vec<TileOption>, WfcError> {
+    if tile.options.is_empty() {
+        return Err(WfcError::EmptyOptions {
+            cell: (tile.i, tile.j),
+        });
+    }
+    let mut keyed_options: Vec<(f64, TileOption)> = tile
+        .options
+        .iter()
+        .map(|opt| {
+            let weight: f64 = tileset.tiles[opt.index()].weight as f64;
+            (rng.random::<f64>().powf(1.0 / weight), opt)
+        })
+        .collect();
+    keyed_options.sort_by(|a, b| b.0.total_cmp(&a.0));
+    Ok(keyed_options.into_iter().map(|(_, opt)| opt).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    // any tile fits next to any other, b is nine times as heavy as a
+    const WEIGHTED: &str = r#"(
+        name: "weighted",
+        tiles: [
+            (name: "a", image: "a.png", sockets: (left: "0", right: "0", top: "0", btm: "0")),
+            (name: "b", image: "b.png", weight: 9.0, sockets: (left: "0", right: "0", top: "0", btm: "0")),
+        ],
+    )"#;
+
+    #[test]
+    fn weights_bias_the_option_order() {
+        let tileset: Tileset = Tileset::from_ron(WEIGHTED).unwrap();
+        let tile: Tile = Tile::new(0, 0, tileset.all_options());
+        let b: TileOption = tileset.find_tile("b").unwrap();
+        let mut rng: StdRng = StdRng::seed_from_u64(0);
+        let runs: usize = 10_000;
+        let b_first: usize = (0..runs)
+            .filter(|_| shuffle_tile_options(&tile, &tileset, &mut rng).unwrap()[0] == b)
+            .count();
+        // b comes first 9 times out of 10
+        let share: f64 = b_first as f64 / runs as f64;
+        assert!((share - 0.9).abs() < 0.02, "b first in {share} of the runs");
+    }
+
+    #[test]
+    fn entropy_depends_on_the_weights() {
+        let mut tileset: Tileset = Tileset::from_ron(WEIGHTED).unwrap();
+        let a: TileOption = tileset.find_tile("a").unwrap();
+        // a single option leaves no choice whatever its weight
+        let single: Tile = Tile::new(0, 0, OptionSet::single(a));
+        assert_eq!(entropy(&single, &tileset), 0.0);
+
+        let both: Tile = Tile::new(0, 0, tileset.all_options());
+        let skewed: f32 = entropy(&both, &tileset);
+        tileset.set_weight("b", 1.0).unwrap();
+        let even: f32 = entropy(&both, &tileset);
+        assert!((even - 2f32.ln()).abs() < ENTROPY_EPSILON);
+        // 1:9 is nearly decided already
+        assert!(skewed < even);
+    }
+}
@@ -0,0 +1,32 @@
+use crate::options::OptionSet;
+
+// Index of a tile in the Tileset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileOption(pub usize);
+
+impl TileOption {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tile {
+    pub collapsed: bool,
+    pub options: OptionSet,
+    pub i: usize,
+    pub j: usize,
+    pub can_be_collapsed: bool,
+}
+
+impl Tile {
+    pub fn new(i: usize, j: usize, options: OptionSet) -> Tile {
+        Tile {
+            collapsed: false,
+            options,
+            i,
+            j,
+            can_be_collapsed: true,
+        }
+    }
+}
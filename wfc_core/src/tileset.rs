@@ -0,0 +1,516 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::WfcError;
+use crate::grid::SIDES;
+use crate::options::{OptionSet, MAX_TILES};
+use crate::tile::TileOption;
+
+// One tile of a tileset manifest. Adjacency is given either by sockets, or
+// by side lists naming the tiles which may sit on that side of this tile.
+// A tile with sockets and a symmetry class is expanded into its distinct
+// rotated and mirrored variants, each drawn as the base image turned by
+// `rotation` quarter turns clockwise after flipping it if `mirrored`.
+// `ascii` is the character for text output, an expanded tile can give one
+// character per variant in the order of Symmetry::variants.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TileDef {
+    pub name: String,
+    pub image: String,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    #[serde(default)]
+    pub ascii: Option<String>,
+    #[serde(default)]
+    pub symmetry: Option<String>,
+    #[serde(skip)]
+    pub rotation: u8,
+    #[serde(skip)]
+    pub mirrored: bool,
+    #[serde(default)]
+    pub sockets: Option<Sockets>,
+    #[serde(default)]
+    pub left: Vec<String>,
+    #[serde(default)]
+    pub right: Vec<String>,
+    #[serde(default)]
+    pub top: Vec<String>,
+    #[serde(default)]
+    pub btm: Vec<String>,
+}
+
+// Edge codes read clockwise around the tile, so two tiles fit together when
+// the code of one edge equals the reversed code of the touching edge.
+// "ABC" on the right side of a tile matches "CBA" on the left side of its
+// right neighbour, symmetric codes like "0" or "ABA" match themselves.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Sockets {
+    pub left: String,
+    pub right: String,
+    pub top: String,
+    pub btm: String,
+}
+
+impl Sockets {
+    // sides are in SIDES order
+    pub fn side(&self, side_ind: usize) -> &str {
+        [&self.left, &self.right, &self.top, &self.btm][side_ind]
+    }
+
+    // a quarter turn clockwise, the clockwise reading of each edge is kept
+    pub fn rotated(&self) -> Sockets {
+        Sockets {
+            left: self.btm.clone(),
+            right: self.top.clone(),
+            top: self.left.clone(),
+            btm: self.right.clone(),
+        }
+    }
+
+    // flipped left to right, which turns clockwise readings around
+    pub fn mirrored(&self) -> Sockets {
+        let rev = |edge: &String| -> String { edge.chars().rev().collect() };
+        Sockets {
+            left: rev(&self.right),
+            right: rev(&self.left),
+            top: rev(&self.top),
+            btm: rev(&self.btm),
+        }
+    }
+}
+
+// Symmetry classes, named after the letter whose shape has the same
+// symmetry. F has none, so it gets all 8 rotations and reflections.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symmetry {
+    X,
+    I,
+    Backslash,
+    T,
+    L,
+    F,
+}
+
+impl Symmetry {
+    pub fn parse(symmetry: &str) -> Result<Symmetry, WfcError> {
+        match symmetry {
+            "X" => Ok(Symmetry::X),
+            "I" => Ok(Symmetry::I),
+            "\\" => Ok(Symmetry::Backslash),
+            "T" => Ok(Symmetry::T),
+            "L" => Ok(Symmetry::L),
+            "F" => Ok(Symmetry::F),
+            _ => Err(WfcError::Tileset(format!("unknown symmetry: {symmetry}"))),
+        }
+    }
+
+    // (quarter turns clockwise, mirrored) of every distinct variant
+    pub fn variants(&self) -> &'static [(u8, bool)] {
+        match self {
+            Symmetry::X => &[(0, false)],
+            Symmetry::I | Symmetry::Backslash => &[(0, false), (1, false)],
+            Symmetry::T | Symmetry::L => &[(0, false), (1, false), (2, false), (3, false)],
+            Symmetry::F => &[
+                (0, false),
+                (1, false),
+                (2, false),
+                (3, false),
+                (0, true),
+                (1, true),
+                (2, true),
+                (3, true),
+            ],
+        }
+    }
+}
+
+// index of the touching side of the neighbour, in SIDES order
+pub(crate) const OPPOSITE: [usize; 4] = [1, 0, 3, 2];
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Manifest {
+    name: String,
+    tiles: Vec<TileDef>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tileset {
+    pub name: String,
+    pub tiles: Vec<TileDef>,
+    // rules[tile][side] are the options allowed on that side of the tile,
+    // sides are in SIDES order.
+    rules: Vec<[OptionSet; 4]>,
+}
+
+impl Tileset {
+    pub fn load(path: impl AsRef<Path>) -> Result<Tileset, WfcError> {
+        let path: &Path = path.as_ref();
+        let manifest: String = std::fs::read_to_string(path)
+            .map_err(|err| WfcError::Tileset(format!("can't read {}: {err}", path.display())))?;
+        Tileset::from_ron(&manifest)
+    }
+
+    pub fn from_ron(manifest: &str) -> Result<Tileset, WfcError> {
+        let manifest: Manifest = ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_str(manifest)
+            .map_err(|err| WfcError::Tileset(err.to_string()))?;
+        if manifest.tiles.is_empty() {
+            return Err(WfcError::Tileset(format!(
+                "tileset {} has no tiles",
+                manifest.name
+            )));
+        }
+
+        let tiles: Vec<TileDef> = expand_variants(manifest.tiles)?;
+        if tiles.len() > MAX_TILES {
+            return Err(WfcError::Tileset(format!(
+                "tileset {} has {} tiles, at most {MAX_TILES} are supported",
+                manifest.name,
+                tiles.len()
+            )));
+        }
+        for tile in &tiles {
+            check_weight(&tile.name, tile.weight)?;
+        }
+        let with_sockets: usize = tiles.iter().filter(|tile| tile.sockets.is_some()).count();
+        let rules: Vec<[OptionSet; 4]> = if with_sockets == tiles.len() {
+            socket_rules(&tiles)
+        } else if with_sockets == 0 {
+            list_rules(&tiles)?
+        } else {
+            return Err(WfcError::Tileset(format!(
+                "tileset {} mixes sockets and side lists",
+                manifest.name
+            )));
+        };
+
+        Ok(Tileset {
+            name: manifest.name,
+            tiles,
+            rules,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    pub fn find_tile(&self, name: &str) -> Option<TileOption> {
+        self.tiles
+            .iter()
+            .position(|tile| tile.name == name)
+            .map(TileOption)
+    }
+
+    // Weights are relative, a tile with weight 2 shows up about twice as
+    // often as a tile with weight 1.
+    pub fn set_weight(&mut self, name: &str, weight: f32) -> Result<(), WfcError> {
+        check_weight(name, weight)?;
+        let Some(tile_opt) = self.find_tile(name) else {
+            return Err(WfcError::UnknownTile(name.to_string()));
+        };
+        self.tiles[tile_opt.index()].weight = weight;
+        Ok(())
+    }
+
+    pub fn all_options(&self) -> OptionSet {
+        OptionSet::full(self.len())
+    }
+
+    pub fn possible_options(
+        &self,
+        tile_opt: &TileOption,
+        side: &str,
+    ) -> Result<OptionSet, WfcError> {
+        let Some(side_ind) = SIDES.iter().position(|s| *s == side) else {
+            return Err(WfcError::UnknownSide(side.to_string()));
+        };
+        Ok(self.compatible(tile_opt, side_ind))
+    }
+
+    // Same as possible_options with the side given by its index in SIDES,
+    // for the propagation loop.
+    pub fn compatible(&self, tile_opt: &TileOption, side_ind: usize) -> OptionSet {
+        self.rules[tile_opt.index()][side_ind]
+    }
+}
+
+fn check_weight(name: &str, weight: f32) -> Result<(), WfcError> {
+    if !weight.is_finite() || weight <= 0.0 {
+        return Err(WfcError::Tileset(format!(
+            "tile {name} has weight {weight}, weights must be positive"
+        )));
+    }
+    Ok(())
+}
+
+fn expand_variants(tiles: Vec<TileDef>) -> Result<Vec<TileDef>, WfcError> {
+    let mut variants: Vec<TileDef> = vec![];
+    for tile in tiles {
+        let Some(symmetry) = &tile.symmetry else {
+            variants.push(tile);
+            continue;
+        };
+        let symmetry: Symmetry = Symmetry::parse(symmetry)?;
+        let Some(sockets) = &tile.sockets else {
+            return Err(WfcError::Tileset(format!(
+                "tile {} has a symmetry but no sockets",
+                tile.name
+            )));
+        };
+
+        let glyphs: Vec<char> = tile
+            .ascii
+            .as_deref()
+            .map(|ascii| ascii.chars().collect())
+            .unwrap_or_default();
+        for (variant_ind, &(rotation, mirrored)) in symmetry.variants().iter().enumerate() {
+            let mut variant_sockets: Sockets = if mirrored {
+                sockets.mirrored()
+            } else {
+                sockets.clone()
+            };
+            for _ in 0..rotation {
+                variant_sockets = variant_sockets.rotated();
+            }
+
+            let mut name: String = tile.name.clone();
+            if mirrored {
+                name.push_str("_m");
+            }
+            if rotation > 0 {
+                name.push_str(&format!("_{}", rotation as u32 * 90));
+            }
+
+            let glyph: Option<char> = match glyphs.len() {
+                1 => Some(glyphs[0]),
+                _ => glyphs.get(variant_ind).copied(),
+            };
+            variants.push(TileDef {
+                name,
+                ascii: glyph.map(String::from),
+                rotation,
+                mirrored,
+                sockets: Some(variant_sockets),
+                ..tile.clone()
+            });
+        }
+    }
+    Ok(variants)
+}
+
+fn list_rules(tiles: &[TileDef]) -> Result<Vec<[OptionSet; 4]>, WfcError> {
+    let find_tile = |name: &String| -> Result<TileOption, WfcError> {
+        match tiles.iter().position(|tile| tile.name == *name) {
+            Some(ind) => Ok(TileOption(ind)),
+            None => Err(WfcError::UnknownTile(name.clone())),
+        }
+    };
+    let mut rules: Vec<[OptionSet; 4]> = vec![];
+    for tile in tiles {
+        let mut tile_rules: [OptionSet; 4] = Default::default();
+        for (side_rules, names) in
+            tile_rules
+                .iter_mut()
+                .zip([&tile.left, &tile.right, &tile.top, &tile.btm])
+        {
+            for name in names {
+                side_rules.insert(find_tile(name)?);
+            }
+        }
+        rules.push(tile_rules);
+    }
+    Ok(rules)
+}
+
+fn socket_rules(tiles: &[TileDef]) -> Vec<[OptionSet; 4]> {
+    let sockets: Vec<&Sockets> = tiles
+        .iter()
+        .filter_map(|tile| tile.sockets.as_ref())
+        .collect();
+    let mut rules: Vec<[OptionSet; 4]> = vec![];
+    for tile_sockets in &sockets {
+        let mut tile_rules: [OptionSet; 4] = Default::default();
+        for (side_ind, side_rules) in tile_rules.iter_mut().enumerate() {
+            let edge: &str = tile_sockets.side(side_ind);
+            for (other_ind, other_sockets) in sockets.iter().enumerate() {
+                let other_edge: String = other_sockets
+                    .side(OPPOSITE[side_ind])
+                    .chars()
+                    .rev()
+                    .collect();
+                if edge == other_edge {
+                    side_rules.insert(TileOption(other_ind));
+                }
+            }
+        }
+        rules.push(tile_rules);
+    }
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // no symmetry at all, so every edge reads differently both ways
+    const ARROW: &str = r#"(
+        name: "arrow",
+        tiles: [
+            (name: "f", image: "f.png", symmetry: "F", sockets: (left: "ab", right: "cd", top: "ef", btm: "gh")),
+            (name: "g", image: "g.png", sockets: (left: "dc", right: "xy", top: "yx", btm: "fe")),
+        ],
+    )"#;
+
+    // names of the tiles allowed on the side of the tile, sorted
+    fn allowed(tileset: &Tileset, name: &str, side_ind: usize) -> Vec<String> {
+        let mut names: Vec<String> = tileset
+            .compatible(&tileset.find_tile(name).unwrap(), side_ind)
+            .iter()
+            .map(|tile_opt| tileset.tiles[tile_opt.index()].name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn sockets_fit_when_reversed() {
+        let tileset: Tileset = Tileset::from_ron(ARROW).unwrap();
+        // "cd" on the right of f fits "dc" on the left of g and of f
+        // mirrored, not "cd"
+        assert_eq!(allowed(&tileset, "f", 1), vec!["f_m", "g"]);
+        assert_eq!(allowed(&tileset, "g", 0), vec!["f"]);
+        // no left side is "yx", the reverse of "xy"
+        assert!(allowed(&tileset, "g", 1).is_empty());
+    }
+
+    #[test]
+    fn weights_must_be_positive() {
+        let mut tileset: Tileset = Tileset::from_ron(ARROW).unwrap();
+        for weight in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                tileset.set_weight("g", weight),
+                Err(WfcError::Tileset(_))
+            ));
+        }
+        assert_eq!(
+            tileset.set_weight("h", 2.0),
+            Err(WfcError::UnknownTile("h".to_string()))
+        );
+        tileset.set_weight("g", 2.0).unwrap();
+        assert_eq!(
+            tileset.tiles[tileset.find_tile("g").unwrap().index()].weight,
+            2.0
+        );
+
+        let zero: String = ARROW.replace(r#"image: "g.png","#, r#"image: "g.png", weight: 0.0,"#);
+        assert!(matches!(
+            Tileset::from_ron(&zero),
+            Err(WfcError::Tileset(_))
+        ));
+    }
+
+    #[test]
+    fn default_tileset_rules() {
+        let tileset: Tileset = Tileset::from_ron(include_str!("../../assets/tiles.ron")).unwrap();
+        let names: Vec<&str> = tileset
+            .tiles
+            .iter()
+            .map(|tile| tile.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["blank", "up", "up_90", "up_180", "up_270"]);
+        // the open side of each rotation of the T-piece faces blank
+        assert_eq!(allowed(&tileset, "blank", 1), vec!["blank", "up_90"]);
+        assert_eq!(allowed(&tileset, "up", 3), vec!["blank", "up_180"]);
+        assert_eq!(allowed(&tileset, "up", 0), vec!["up", "up_180", "up_90"]);
+    }
+
+    #[test]
+    fn variants_have_turned_sockets() {
+        let tileset: Tileset = Tileset::from_ron(ARROW).unwrap();
+        let variant =
+            |name: &str| -> &TileDef { &tileset.tiles[tileset.find_tile(name).unwrap().index()] };
+        assert_eq!(tileset.len(), 9);
+
+        let base: Sockets = variant("f").sockets.clone().unwrap();
+        let turned: &TileDef = variant("f_90");
+        assert_eq!((turned.rotation, turned.mirrored), (1, false));
+        assert_eq!(
+            turned.sockets,
+            Some(Sockets {
+                left: String::from("gh"),
+                right: String::from("ef"),
+                top: String::from("ab"),
+                btm: String::from("cd"),
+            })
+        );
+        assert_eq!(
+            variant("f_270").sockets,
+            Some(base.rotated().rotated().rotated())
+        );
+
+        let flipped: &TileDef = variant("f_m");
+        assert_eq!((flipped.rotation, flipped.mirrored), (0, true));
+        assert_eq!(
+            flipped.sockets,
+            Some(Sockets {
+                left: String::from("dc"),
+                right: String::from("ba"),
+                top: String::from("fe"),
+                btm: String::from("hg"),
+            })
+        );
+        // flipping twice gives the tile back
+        assert_eq!(base.mirrored().mirrored(), base);
+        assert_eq!(
+            variant("f_m_180").sockets,
+            Some(base.mirrored().rotated().rotated())
+        );
+    }
+
+    #[test]
+    fn variant_counts_follow_symmetry() {
+        for (symmetry, count) in [
+            ("X", 1),
+            ("I", 2),
+            ("\\\\", 2),
+            ("T", 4),
+            ("L", 4),
+            ("F", 8),
+        ] {
+            let manifest: String = format!(
+                r#"(name: "one", tiles: [(name: "t", image: "t.png", symmetry: "{symmetry}", sockets: (left: "0", right: "1", top: "2", btm: "3"))])"#
+            );
+            assert_eq!(
+                Tileset::from_ron(&manifest).unwrap().len(),
+                count,
+                "{symmetry}"
+            );
+        }
+    }
+
+    #[test]
+    fn socket_rules_are_symmetric() {
+        let tileset: Tileset = Tileset::from_ron(ARROW).unwrap();
+        for tile_opt in tileset.all_options().iter() {
+            for (side_ind, opposite_ind) in OPPOSITE.iter().enumerate() {
+                for other in tileset.all_options().iter() {
+                    assert_eq!(
+                        tileset.compatible(&tile_opt, side_ind).contains(other),
+                        tileset.compatible(&other, *opposite_ind).contains(tile_opt)
+                    );
+                }
+            }
+        }
+    }
+}
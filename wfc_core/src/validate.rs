@@ -0,0 +1,118 @@
+use std::fmt;
+
+use crate::grid::{Grid, SIDES};
+use crate::tileset::Tileset;
+
+// A collapsed cell whose collapsed neighbour on `side` isn't allowed there
+// by the rules of the cell's tile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub cell: (usize, usize),
+    pub side: &'static str,
+    pub neighbour: (usize, usize),
+    pub tile: String,
+    pub neighbour_tile: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {:?} doesn't allow {} at {:?} on its {} side",
+            self.tile, self.cell, self.neighbour_tile, self.neighbour, self.side
+        )
+    }
+}
+
+// Checks every collapsed cell against its collapsed neighbours in all four
+// directions. Each direction is checked on its own, so a pair breaking the
+// rules of both its tiles shows up twice, and with asymmetric rules only
+// once. Cells which aren't collapsed are skipped.
+pub fn validate(grid: &Grid, tileset: &Tileset) -> Vec<Violation> {
+    let mut violations: Vec<Violation> = vec![];
+    for tile in &grid.tiles {
+        let Some(tile_opt) = tile.options.first().filter(|_| tile.collapsed) else {
+            continue;
+        };
+        for (side_ind, side) in SIDES.iter().enumerate() {
+            let Ok(Some(neighbour_ind)) = grid.neighbour_ind(tile.i, tile.j, side) else {
+                continue;
+            };
+            let neighbour = &grid.tiles[neighbour_ind];
+            let Some(neighbour_opt) = neighbour.options.first().filter(|_| neighbour.collapsed)
+            else {
+                continue;
+            };
+            if !tileset
+                .compatible(&tile_opt, side_ind)
+                .contains(neighbour_opt)
+            {
+                violations.push(Violation {
+                    cell: (tile.i, tile.j),
+                    side,
+                    neighbour: (neighbour.i, neighbour.j),
+                    tile: tileset.tiles[tile_opt.index()].name.clone(),
+                    neighbour_tile: tileset.tiles[neighbour_opt.index()].name.clone(),
+                });
+            }
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtrack::Solver;
+    use crate::options::OptionSet;
+
+    const TILESET: &str = include_str!("../../assets/tiles.ron");
+
+    fn set(grid: &mut Grid, tileset: &Tileset, grid_ind: usize, name: &str) {
+        grid.tiles[grid_ind].options = OptionSet::single(tileset.find_tile(name).unwrap());
+        grid.tiles[grid_ind].collapsed = true;
+    }
+
+    #[test]
+    fn solved_grid_is_valid() {
+        let tileset: Tileset = Tileset::from_ron(TILESET).unwrap();
+        let mut solver: Solver = Solver::new(Grid::new(12, 9, 5, &tileset), tileset.clone(), 1000);
+        solver.run().unwrap();
+        assert!(validate(&solver.grid, &tileset).is_empty());
+    }
+
+    #[test]
+    fn finds_pairs_which_dont_fit() {
+        let tileset: Tileset = Tileset::from_ron(TILESET).unwrap();
+        let mut grid: Grid = Grid::new(3, 2, 0, &tileset);
+        // the left arm of up runs into blank, up_180 below it fits
+        set(&mut grid, &tileset, 0, "blank");
+        set(&mut grid, &tileset, 1, "up");
+        set(&mut grid, &tileset, 4, "up_180");
+        // (0, 2) and (1, 0) aren't collapsed, so they're skipped
+        let violations: Vec<Violation> = validate(&grid, &tileset);
+        assert_eq!(
+            violations,
+            vec![
+                Violation {
+                    cell: (0, 0),
+                    side: "right",
+                    neighbour: (0, 1),
+                    tile: String::from("blank"),
+                    neighbour_tile: String::from("up"),
+                },
+                Violation {
+                    cell: (0, 1),
+                    side: "left",
+                    neighbour: (0, 0),
+                    tile: String::from("up"),
+                    neighbour_tile: String::from("blank"),
+                },
+            ]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "blank at (0, 0) doesn't allow up at (0, 1) on its right side"
+        );
+    }
+}
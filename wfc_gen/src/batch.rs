@@ -0,0 +1,240 @@
+use std::fmt::Write as _;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use wfc_core::{Solver, Tileset};
+
+use crate::{fail, make_solver, render, Cli, Format, EXIT_CONTRADICTION, EXIT_ERROR};
+
+// Outcome of one map of the batch.
+struct Run {
+    seed: u64,
+    solved: bool,
+    backtracks: usize,
+    restarts: usize,
+    time: Duration,
+}
+
+// Generates count maps with the seeds base..base+count into out_dir and
+// writes summary.txt next to them. Maps the solver couldn't finish are
+// counted in the summary but not written.
+pub fn run_batch(cli: &Cli, tileset: Tileset, base: u64, count: u64, out_dir: &Path) -> ExitCode {
+    if let Err(err) = std::fs::create_dir_all(out_dir) {
+        return fail(
+            EXIT_ERROR,
+            &format!("can't create {}: {err}", out_dir.display()),
+        );
+    }
+    let mut runs: Vec<Run> = Vec::with_capacity(count as usize);
+    let mut frequency: Vec<u64> = vec![0; tileset.len()];
+    let mut tileset: Tileset = tileset;
+    for seed in (0..count).map(|n| base.wrapping_add(n)) {
+        let mut solver: Solver = match make_solver(cli, tileset, seed) {
+            Ok(solver) => solver,
+            Err(err) => return fail(EXIT_ERROR, &err.to_string()),
+        };
+        let started: Instant = Instant::now();
+        let solved: bool = solver.run().is_ok();
+        runs.push(Run {
+            seed,
+            solved,
+            backtracks: solver.backtracks,
+            restarts: solver.restarts,
+            time: started.elapsed(),
+        });
+        if solved {
+            for tile in &solver.grid.tiles {
+                if let Some(tile_opt) = tile.options.first() {
+                    frequency[tile_opt.index()] += 1;
+                }
+            }
+            let output: Vec<u8> = match render(cli, &solver) {
+                Ok(output) => output,
+                Err(err) => return fail(EXIT_ERROR, &err.to_string()),
+            };
+            let path = out_dir.join(format!("map_{seed}.{}", extension(cli.format)));
+            if let Err(err) = std::fs::write(&path, output) {
+                return fail(
+                    EXIT_ERROR,
+                    &format!("can't write {}: {err}", path.display()),
+                );
+            }
+        } else {
+            eprintln!("wfc-gen: seed {seed} failed");
+        }
+        tileset = solver.tileset;
+    }
+
+    let report: String = summary(&runs, &tileset, &frequency);
+    print!("{report}");
+    let path = out_dir.join("summary.txt");
+    if let Err(err) = std::fs::write(&path, &report) {
+        return fail(
+            EXIT_ERROR,
+            &format!("can't write {}: {err}", path.display()),
+        );
+    }
+    if runs.iter().all(|run| run.solved) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_CONTRADICTION)
+    }
+}
+
+fn extension(format: Format) -> &'static str {
+    match format {
+        Format::Png => "png",
+        Format::Json => "json",
+        Format::Ascii => "txt",
+    }
+}
+
+fn summary(runs: &[Run], tileset: &Tileset, frequency: &[u64]) -> String {
+    let solved: Vec<&Run> = runs.iter().filter(|run| run.solved).collect();
+    let mut report: String = String::new();
+    let _ = writeln!(report, "tileset: {}", tileset.name);
+    let _ = writeln!(
+        report,
+        "seeds: {}..{}",
+        runs.first().map_or(0, |run| run.seed),
+        runs.last().map_or(0, |run| run.seed.wrapping_add(1))
+    );
+    let _ = writeln!(
+        report,
+        "solved: {}/{} ({:.1}%)",
+        solved.len(),
+        runs.len(),
+        percent(solved.len() as u64, runs.len() as u64)
+    );
+    let backtracks: usize = runs.iter().map(|run| run.backtracks).sum();
+    let _ = writeln!(
+        report,
+        "backtracks: {:.2} on average, {} at most",
+        backtracks as f64 / runs.len().max(1) as f64,
+        runs.iter().map(|run| run.backtracks).max().unwrap_or(0)
+    );
+    let restarts: usize = runs.iter().map(|run| run.restarts).sum();
+    let _ = writeln!(
+        report,
+        "restarts: {:.2} on average",
+        restarts as f64 / runs.len().max(1) as f64
+    );
+
+    // percentiles of every run, failed runs took time too
+    let mut times: Vec<Duration> = runs.iter().map(|run| run.time).collect();
+    times.sort();
+    let _ = writeln!(report, "solve time:");
+    for (label, quantile) in [("p50", 0.5), ("p90", 0.9), ("p99", 0.99), ("max", 1.0)] {
+        let _ = writeln!(report, "  {label:<4} {:?}", percentile(&times, quantile));
+    }
+
+    // cells of the solved maps per tile, the tiles most used first
+    let total: u64 = frequency.iter().sum();
+    let mut tile_inds: Vec<usize> = (0..frequency.len()).collect();
+    tile_inds.sort_by(|a, b| frequency[*b].cmp(&frequency[*a]).then(a.cmp(b)));
+    let width: usize = tileset
+        .tiles
+        .iter()
+        .map(|tile| tile.name.len())
+        .max()
+        .unwrap_or(0);
+    let _ = writeln!(report, "tile frequency:");
+    for tile_ind in tile_inds {
+        let share: f64 = percent(frequency[tile_ind], total);
+        let _ = writeln!(
+            report,
+            "  {:<width$} {:>8} {:>5.1}% {}",
+            tileset.tiles[tile_ind].name,
+            frequency[tile_ind],
+            share,
+            "#".repeat((share / 2.0).round() as usize),
+        );
+    }
+    report
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    part as f64 * 100.0 / total as f64
+}
+
+// Nearest-rank percentile of sorted times.
+fn percentile(times: &[Duration], quantile: f64) -> Duration {
+    if times.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank: usize = (quantile * times.len() as f64).ceil() as usize;
+    times[rank.clamp(1, times.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(seed: u64, solved: bool, backtracks: usize, millis: u64) -> Run {
+        Run {
+            seed,
+            solved,
+            backtracks,
+            restarts: 0,
+            time: Duration::from_millis(millis),
+        }
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let one: Vec<Duration> = vec![Duration::from_millis(7)];
+        for quantile in [0.0, 0.5, 0.99, 1.0] {
+            assert_eq!(percentile(&one, quantile), Duration::from_millis(7));
+        }
+        assert_eq!(percentile(&[], 0.5), Duration::ZERO);
+
+        // 1ms to 100ms, the p-th percentile is p ms
+        let times: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&times, 0.5), Duration::from_millis(50));
+        assert_eq!(percentile(&times, 0.9), Duration::from_millis(90));
+        assert_eq!(percentile(&times, 0.99), Duration::from_millis(99));
+        assert_eq!(percentile(&times, 1.0), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn summary_counts_the_runs() {
+        let tileset: Tileset = Tileset::from_ron(include_str!("../../assets/tiles.ron")).unwrap();
+        let runs: Vec<Run> = vec![
+            run(10, true, 0, 4),
+            run(11, false, 6, 1),
+            run(12, true, 2, 3),
+            run(13, true, 0, 2),
+        ];
+        // blank, up, up_90, up_180, up_270
+        let frequency: Vec<u64> = vec![5, 20, 1, 20, 0];
+        let report: String = summary(&runs, &tileset, &frequency);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "tileset: basic");
+        assert_eq!(lines[1], "seeds: 10..14");
+        assert_eq!(lines[2], "solved: 3/4 (75.0%)");
+        assert_eq!(lines[3], "backtracks: 2.00 on average, 6 at most");
+        assert_eq!(lines[6], "  p50  2ms");
+        assert_eq!(lines[9], "  max  4ms");
+
+        // the most used tiles first, ties in tileset order
+        let order: Vec<&str> = lines[11..]
+            .iter()
+            .map(|line| line.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(order, vec!["up", "up_180", "blank", "up_90", "up_270"]);
+        assert!(lines[11].ends_with(" 43.5% ######################"));
+        assert!(lines[15].ends_with("   0.0% "));
+    }
+
+    #[test]
+    fn empty_batch_has_no_rates() {
+        let tileset: Tileset = Tileset::from_ron(include_str!("../../assets/tiles.ron")).unwrap();
+        let report: String = summary(&[], &tileset, &[0; 5]);
+        assert!(report.contains("solved: 0/0 (0.0%)"));
+        assert!(report.contains("backtracks: 0.00 on average, 0 at most"));
+    }
+}
@@ -0,0 +1,188 @@
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+use wfc_core::{analyze_rules, RuleReport};
+use wfc_core::{cell_selector_by_name, value_selector_by_name, Grid, Solver, Tileset, WfcError};
+use wfc_core::{encode_png, render_ascii, render_grid, RenderOptions};
+
+mod batch;
+
+// Exit codes: 0 is a finished map, EXIT_CONTRADICTION means the solver
+// couldn't finish the map, EXIT_ERROR is bad input or a failed write.
+// clap exits with 2 on bad arguments as well. `check` exits with
+// EXIT_RULE_PROBLEMS if it found anything.
+const EXIT_CONTRADICTION: u8 = 1;
+const EXIT_RULE_PROBLEMS: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Png,
+    Json,
+    Ascii,
+}
+
+/// Generates a wave function collapse map without a window or GPU.
+#[derive(Debug, Parser)]
+#[command(name = "wfc-gen", version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(long, default_value = "7")]
+    width: NonZeroUsize,
+    #[arg(long, default_value = "7")]
+    height: NonZeroUsize,
+    /// Random if not given, the seed is printed to stderr. With --count it's
+    /// the seed of the first map
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Number of maps to generate into --out-dir, with the seeds
+    /// seed..seed+count, a summary.txt is written next to them
+    #[arg(long, requires = "out_dir", conflicts_with = "output")]
+    count: Option<u64>,
+    #[arg(long, requires = "count")]
+    out_dir: Option<PathBuf>,
+    #[arg(long, default_value = "assets/tiles.ron")]
+    tileset: PathBuf,
+    /// Folder with the tile images, default is the folder of the tileset
+    #[arg(long)]
+    assets: Option<PathBuf>,
+    #[arg(long, value_enum, default_value_t = Format::Ascii)]
+    format: Format,
+    /// File to write, stdout if not given or "-"
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// Tile size in pixels for PNG output
+    #[arg(long, default_value_t = 50)]
+    tile_size: u32,
+    /// Gap between tiles in pixels for PNG output
+    #[arg(long, default_value_t = 10)]
+    gap: u32,
+    #[arg(long, default_value = "min-entropy")]
+    cell_selector: String,
+    #[arg(long, default_value = "weighted")]
+    value_selector: String,
+    /// Most backtracks in one attempt, the solver starts over with longer
+    /// attempts until one needs more than this
+    #[arg(long, default_value_t = 1000)]
+    max_backtracks: usize,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Checks the adjacency rules of a tileset for asymmetric pairs, dead
+    /// ends and tiles which can never appear
+    Check {
+        #[arg(long, default_value = "assets/tiles.ron")]
+        tileset: PathBuf,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli: Cli = Cli::parse();
+    if let Some(Command::Check { tileset }) = &cli.command {
+        return check(tileset);
+    }
+    let tileset: Tileset = match Tileset::load(&cli.tileset) {
+        Ok(tileset) => tileset,
+        Err(err) => return fail(EXIT_ERROR, &err.to_string()),
+    };
+    let report: RuleReport = analyze_rules(&tileset);
+    for line in report.to_string().lines() {
+        eprintln!("wfc-gen: warning: {line}");
+    }
+    let seed: u64 = cli.seed.unwrap_or_else(rand::random);
+    eprintln!("wfc-gen: seed {seed}");
+    if let (Some(count), Some(out_dir)) = (cli.count, &cli.out_dir) {
+        return batch::run_batch(&cli, tileset, seed, count, out_dir);
+    }
+
+    let mut solver: Solver = match make_solver(&cli, tileset, seed) {
+        Ok(solver) => solver,
+        Err(err) => return fail(EXIT_ERROR, &err.to_string()),
+    };
+    if let Err(err) = solver.run() {
+        if let Some(explanation) = solver.contradiction() {
+            eprintln!("wfc-gen: last contradiction: {explanation}");
+        }
+        return fail(EXIT_CONTRADICTION, &err.to_string());
+    }
+
+    let output: Vec<u8> = match render(&cli, &solver) {
+        Ok(output) => output,
+        Err(err) => return fail(EXIT_ERROR, &err.to_string()),
+    };
+    if let Err(err) = write_output(cli.output.as_deref(), &output) {
+        return fail(EXIT_ERROR, &err.to_string());
+    }
+    ExitCode::SUCCESS
+}
+
+fn fail(code: u8, msg: &str) -> ExitCode {
+    eprintln!("wfc-gen: {msg}");
+    ExitCode::from(code)
+}
+
+fn check(path: &Path) -> ExitCode {
+    let tileset: Tileset = match Tileset::load(path) {
+        Ok(tileset) => tileset,
+        Err(err) => return fail(EXIT_ERROR, &err.to_string()),
+    };
+    let report: RuleReport = analyze_rules(&tileset);
+    if report.is_empty() {
+        println!(
+            "tileset {}: {} tiles, no problems found",
+            tileset.name,
+            tileset.len()
+        );
+        return ExitCode::SUCCESS;
+    }
+    print!("{report}");
+    ExitCode::from(EXIT_RULE_PROBLEMS)
+}
+
+fn make_solver(cli: &Cli, tileset: Tileset, seed: u64) -> Result<Solver, WfcError> {
+    let grid: Grid = Grid::new(cli.width.get(), cli.height.get(), seed, &tileset);
+    Ok(Solver::new(grid, tileset, cli.max_backtracks)
+        .with_cell_selector(cell_selector_by_name(&cli.cell_selector)?)
+        .with_value_selector(value_selector_by_name(&cli.value_selector)?))
+}
+
+fn render(cli: &Cli, solver: &Solver) -> Result<Vec<u8>, WfcError> {
+    match cli.format {
+        Format::Png => {
+            let assets: PathBuf = match &cli.assets {
+                Some(assets) => assets.clone(),
+                None => cli
+                    .tileset
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default(),
+            };
+            let options: RenderOptions = RenderOptions {
+                tile_size: cli.tile_size,
+                gap: cli.gap,
+                ..RenderOptions::default()
+            };
+            encode_png(&render_grid(
+                &solver.grid,
+                &solver.tileset,
+                assets,
+                &options,
+            )?)
+        }
+        Format::Json => Ok(format!("{}\n", solver.grid.to_json(&solver.tileset)?).into_bytes()),
+        Format::Ascii => Ok(render_ascii(&solver.grid, &solver.tileset).into_bytes()),
+    }
+}
+
+fn write_output(path: Option<&Path>, output: &[u8]) -> std::io::Result<()> {
+    match path {
+        Some(path) if path != Path::new("-") => std::fs::write(path, output),
+        _ => std::io::stdout().lock().write_all(output),
+    }
+}